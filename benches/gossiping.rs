@@ -0,0 +1,160 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! Benchmarks of the core operations, at various cluster sizes and numbers of rumors.
+//! Run with `cargo bench --features bench`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use ed25519_dalek::Keypair;
+use safe_gossip::{bench, Config, Content, Gossiping, Id, Player};
+
+// The cluster sizes, counting us, and numbers of rumors benchmarked.
+const PLAYERS: [usize; 4] = [20, 200, 2000, 10_000];
+const RUMORS: [usize; 3] = [1, 10, 100];
+const RUMOR_SIZE: usize = 1024;
+
+fn id(index: usize) -> Id {
+    let mut id = Id([0; 32]);
+    id.0[..8].copy_from_slice(&(index as u64).to_be_bytes());
+    id
+}
+
+fn index(id: Id) -> usize {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&id.0[..8]);
+    u64::from_be_bytes(bytes) as usize
+}
+
+fn content(index: usize) -> Content {
+    let mut value = vec![0; RUMOR_SIZE];
+    value[..8].copy_from_slice(&(index as u64).to_be_bytes());
+    Content::new(value)
+}
+
+// The instance of player `our_index` in a cluster of `players`.
+fn gossiping(our_index: usize, players: usize) -> Gossiping {
+    let others = (0..players)
+        .filter(|index| *index != our_index)
+        .map(|index| Player { id: id(index) })
+        .collect();
+    Gossiping::new(id(our_index), others)
+}
+
+// The instance of player 0, having initiated `rumors`.
+fn with_rumors(players: usize, rumors: usize) -> Gossiping {
+    let mut gossiping = gossiping(0, players);
+    for index in 0..rumors {
+        gossiping
+            .initiate_rumor(content(index))
+            .expect("rumor initiated");
+    }
+    gossiping
+}
+
+fn cases() -> impl Iterator<Item = (usize, usize)> {
+    PLAYERS
+        .iter()
+        .flat_map(|players| RUMORS.iter().map(move |rumors| (*players, *rumors)))
+}
+
+fn initiate_rumor(c: &mut Criterion) {
+    let mut group = c.benchmark_group("initiate_rumor");
+    for (players, rumors) in cases() {
+        let gossiping = with_rumors(players, rumors);
+        group.bench_function(BenchmarkId::new(players.to_string(), rumors), |b| {
+            b.iter_batched(
+                || gossiping.clone(),
+                |mut gossiping| gossiping.initiate_rumor(content(rumors)),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn collect_gossip(c: &mut Criterion) {
+    let mut group = c.benchmark_group("collect_gossip");
+    for (players, rumors) in cases() {
+        let gossiping = with_rumors(players, rumors);
+        group.bench_function(BenchmarkId::new(players.to_string(), rumors), |b| {
+            b.iter_batched(
+                || gossiping.clone(),
+                |mut gossiping| gossiping.collect_gossip(),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+// The player pushed to receiving all rumors from player 0, and responding.
+fn receive_gossip(c: &mut Criterion) {
+    let mut group = c.benchmark_group("receive_gossip");
+    for (players, rumors) in cases() {
+        let push = with_rumors(players, rumors)
+            .collect_gossip()
+            .expect("rumors pushed");
+        let receiver = gossiping(index(push.callee.id), players);
+        group.bench_function(BenchmarkId::new(players.to_string(), rumors), |b| {
+            b.iter_batched(
+                || receiver.clone(),
+                |mut receiver| receiver.receive_gossip(&push, true),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+// A push of `rumors`, as a frame signed with new keys, to player 1.
+fn frame(rumors: usize, config: &Config) -> (Keypair, Vec<u8>) {
+    let keys = Keypair::generate::<sha3::Sha3_512, _>(&mut rand::thread_rng());
+    let mut sender = with_rumors(20, rumors);
+    let push = sender.collect_gossip().expect("rumors pushed");
+    let frame = bench::serialise_push(push, id(1), config, &keys).expect("push serialised");
+    (keys, frame)
+}
+
+fn transmission(c: &mut Criterion) {
+    let config = Config::default();
+    let mut group = c.benchmark_group("serialise");
+    for rumors in RUMORS.iter().copied() {
+        let keys = Keypair::generate::<sha3::Sha3_512, _>(&mut rand::thread_rng());
+        let push = with_rumors(20, rumors)
+            .collect_gossip()
+            .expect("rumors pushed");
+        group.bench_function(BenchmarkId::from_parameter(rumors), |b| {
+            b.iter_batched(
+                || push.clone(),
+                |push| bench::serialise_push(push, id(1), &config, &keys),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("deserialise");
+    for rumors in RUMORS.iter().copied() {
+        let (keys, frame) = frame(rumors, &config);
+        let frames = [(keys.public, frame)];
+        group.bench_function(BenchmarkId::from_parameter(rumors), |b| {
+            b.iter(|| bench::deserialise(&frames, &id(1)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    initiate_rumor,
+    collect_gossip,
+    receive_gossip,
+    transmission
+);
+criterion_main!(benches);
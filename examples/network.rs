@@ -10,7 +10,7 @@
 //! Run a local network of gossiper nodes.
 
 #![forbid(
-    exceeding_bitshifts,
+    arithmetic_overflow,
     mutable_transmutes,
     no_mangle_const_items,
     unknown_crate_types
@@ -42,14 +42,12 @@
     unused_results
 )]
 #![allow(
-    box_pointers,
     missing_copy_implementations,
     missing_debug_implementations,
     variant_size_differences,
     non_camel_case_types
 )]
 
-use rand;
 #[macro_use]
 extern crate unwrap;
 use ed25519_dalek::{Keypair, PublicKey};
@@ -59,8 +57,8 @@ use futures_cpupool::{CpuFuture, CpuPool};
 use rand::distributions::Alphanumeric;
 use rand::Rng;
 use safe_gossip::{
-    ClientChannel, ClientCmd, Content, Error, GossipStepper, Gossiping, Id, Player,
-    PlayerIncomingChannel, PlayerOutgoingChannels,
+    ClientChannel, ClientCmd, Content, Error, GossipStepper, Gossiping, GroupId, Id, Player,
+    PlayerIncomingChannel, PlayerOutgoingChannels, SendError,
 };
 use sha3::Sha3_512;
 use std::collections::{BTreeMap, BTreeSet};
@@ -68,7 +66,7 @@ use std::fmt::{self, Debug, Formatter};
 use std::iter::Iterator;
 use std::mem;
 
-///
+/// Receives the transmissions sent to a node.
 pub struct TestPlayerIncomingChannel {
     receiver: mpsc::UnboundedReceiver<(PublicKey, Vec<u8>)>,
 }
@@ -90,7 +88,7 @@ impl PlayerIncomingChannel for TestPlayerIncomingChannel {
     }
 }
 
-///
+/// Sends transmissions from a node to the other nodes.
 #[derive(Clone)]
 pub struct TestPlayerOutgoingChannels {
     senders: BTreeMap<Id, mpsc::UnboundedSender<(PublicKey, Vec<u8>)>>,
@@ -103,7 +101,11 @@ impl TestPlayerOutgoingChannels {
 }
 
 impl PlayerOutgoingChannels for TestPlayerOutgoingChannels {
-    fn send_to_player(&mut self, id: Id, transmission: (PublicKey, Vec<u8>)) -> Result<(), Error> {
+    fn send_to_player(
+        &mut self,
+        id: Id,
+        transmission: (PublicKey, Vec<u8>),
+    ) -> Result<(), SendError> {
         match self.senders.get_mut(&id) {
             Some(sender) => match sender.unbounded_send(transmission) {
                 Ok(_) => (),
@@ -134,6 +136,8 @@ impl ClientChannel for TestClientChannel {
         }
         None
     }
+
+    fn write_to_client(&mut self, _group: GroupId, _content: Content) {}
 }
 
 struct Network {
@@ -188,7 +192,7 @@ impl Network {
             let other_players = players
                 .iter()
                 .filter(|p| p.id != id) // exclude this player from the list of players
-                .map(|p| *p)
+                .copied()
                 .collect::<BTreeSet<Player>>();
             let node = GossipStepper::new(
                 keys,
@@ -225,9 +229,10 @@ impl Network {
             Some(index) if index < self.client_senders.len() => index,
             _ => rand::thread_rng().gen_range(0, self.client_senders.len()),
         };
-        let cmd = ClientCmd::NewRumor(Content {
-            value: String::from(message).into_bytes(),
-        });
+        let cmd = ClientCmd::NewRumor(
+            GroupId::default(),
+            Content::new(String::from(message).into_bytes()),
+        );
         let player = &self.client_senders.values_mut().collect::<Vec<_>>()[i];
         match player.unbounded_send(cmd) {
             Ok(_) => (),
@@ -263,7 +268,7 @@ impl Drop for Network {
                 _ => println!("error in drop() at network.rs line 263"),
             }
         }
-        let node_futures = mem::replace(&mut self.node_futures, vec![]);
+        let node_futures = mem::take(&mut self.node_futures);
         for node_future in node_futures {
             match node_future.wait() {
                 Ok(_) => (),
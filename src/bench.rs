@@ -0,0 +1,61 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! Entry points for the benchmarks under `benches/`, which measure the (de)serialisation of
+//! transmissions the crate doesn't export.  Only built with the `bench` feature.
+
+use crate::compression::CompressionMetrics;
+use crate::config::{Config, Limits};
+use crate::error::Error;
+use crate::gossip::Gossip;
+use crate::id::{GroupId, Id};
+use crate::signing::NativeSigner;
+use crate::transmission::{Envelope, Message, Transmission, PROTOCOL_VERSION};
+use ed25519_dalek::{Keypair, PublicKey};
+
+/// Serialises `gossip` into a frame pushing it to `recipient`, encoded as per `config` and
+/// signed with `keys`.
+pub fn serialise_push(
+    gossip: Gossip,
+    recipient: Id,
+    config: &Config,
+    keys: &Keypair,
+) -> Result<Vec<u8>, Error> {
+    let envelope = Envelope {
+        recipient,
+        version: PROTOCOL_VERSION,
+        sequence: 0,
+        session: None,
+    };
+    Transmission::serialise(
+        &GroupId::default(),
+        &Message::Push(gossip),
+        &envelope,
+        config,
+        keys,
+        &NativeSigner,
+        &mut CompressionMetrics::default(),
+    )
+}
+
+/// Parses `frames` sent to `our_id`, checking their signatures as one batch, and decodes them,
+/// returning the number of rumors they hold.
+pub fn deserialise(frames: &[(PublicKey, Vec<u8>)], our_id: &Id) -> Result<usize, Error> {
+    let limits = Limits::default();
+    let mut rumors = 0;
+    for transmission in Transmission::deserialise_batch(frames, our_id, &NativeSigner, &limits) {
+        match transmission?.get_value(None)? {
+            Message::Push(gossip) | Message::Response(gossip) | Message::Bootstrap(gossip) => {
+                rumors += gossip.rumors.len()
+            }
+            _ => (),
+        }
+    }
+    Ok(rumors)
+}
@@ -0,0 +1,200 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use crate::error::Error;
+use crate::gossip::Rumor;
+use bincode::Options;
+#[cfg(not(feature = "protobuf"))]
+use serde::{de::DeserializeOwned, Serialize};
+
+/// The encoding of transmissions.
+/// All players of a cluster are expected to use the same codec.  Since each transmission
+/// records the codec it was encoded with, a player can still decode transmissions encoded
+/// with any other codec it was built with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Codec {
+    /// bincode, the default.
+    #[default]
+    Bincode,
+    /// CBOR, as specified in RFC 7049.
+    #[cfg(feature = "cbor")]
+    Cbor,
+    /// Protocol Buffers, as per the schema in `proto/safe_gossip.proto`.
+    #[cfg(feature = "protobuf")]
+    Protobuf,
+}
+
+impl Codec {
+    /// The byte identifying the codec on the wire.
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            Codec::Bincode => 0,
+            #[cfg(feature = "cbor")]
+            Codec::Cbor => 1,
+            #[cfg(feature = "protobuf")]
+            Codec::Protobuf => 2,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> Result<Codec, Error> {
+        match id {
+            0 => Ok(Codec::Bincode),
+            #[cfg(feature = "cbor")]
+            1 => Ok(Codec::Cbor),
+            #[cfg(feature = "protobuf")]
+            2 => Ok(Codec::Protobuf),
+            _ => Err(Error::UnsupportedCodec(id)),
+        }
+    }
+
+    pub(crate) fn encode<T: Wire>(self, value: &T) -> Result<Vec<u8>, Error> {
+        match self {
+            Codec::Bincode => Ok(bincode::serialize(value)?),
+            #[cfg(feature = "cbor")]
+            Codec::Cbor => serde_cbor::to_vec(value).map_err(|e| Error::Encoding(e.to_string())),
+            #[cfg(feature = "protobuf")]
+            Codec::Protobuf => {
+                let mut bytes = vec![];
+                prost::Message::encode(&value.to_proto(), &mut bytes)
+                    .map_err(|e| Error::Encoding(e.to_string()))?;
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// The size of `rumor` once encoded, before any compression.
+    pub(crate) fn rumor_size(self, rumor: &Rumor) -> u64 {
+        match self {
+            Codec::Bincode => bincode::serialized_size(rumor).unwrap_or(0),
+            #[cfg(feature = "cbor")]
+            Codec::Cbor => serde_cbor::to_vec(rumor).map_or(0, |bytes| bytes.len() as u64),
+            #[cfg(feature = "protobuf")]
+            Codec::Protobuf => {
+                prost::Message::encoded_len(&crate::protobuf::rumor_to_proto(rumor)) as u64
+            }
+        }
+    }
+
+    pub(crate) fn decode<T: Wire>(self, bytes: &[u8]) -> Result<T, Error> {
+        match self {
+            // Bounded by the input, so that no length prefix can make it allocate more.
+            Codec::Bincode => Ok(bincode::DefaultOptions::new()
+                .with_fixint_encoding()
+                .allow_trailing_bytes()
+                .with_limit(bytes.len() as u64)
+                .deserialize(bytes)?),
+            #[cfg(feature = "cbor")]
+            Codec::Cbor => {
+                check_cbor_lengths(bytes)?;
+                serde_cbor::from_slice(bytes).map_err(|e| Error::Encoding(e.to_string()))
+            }
+            // prost checks every length against the bytes left, so it's bounded by the input.
+            #[cfg(feature = "protobuf")]
+            Codec::Protobuf => {
+                let proto = prost::Message::decode(bytes)
+                    .map_err(|e: prost::DecodeError| Error::Encoding(e.to_string()))?;
+                T::from_proto(proto)
+            }
+        }
+    }
+}
+
+// Checks that no length in the CBOR `bytes` claims more than the bytes left, so that decoding
+// them is bounded by the input like bincode's.  Each item takes at least a byte, so an array
+// can't hold more items than there are bytes left, nor a map more than half as many entries.
+// Anything else amiss is left to the decoder.
+#[cfg(feature = "cbor")]
+fn check_cbor_lengths(bytes: &[u8]) -> Result<(), Error> {
+    let beyond = || Error::Encoding("CBOR length beyond the end of the input".to_string());
+    let mut position = 0;
+    while let Some(&initial) = bytes.get(position) {
+        position += 1;
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        let argument = match info {
+            0..=23 => u64::from(info),
+            24..=27 => {
+                let size = 1 << (info - 24);
+                let argument = bytes.get(position..position + size).ok_or_else(beyond)?;
+                position += size;
+                argument
+                    .iter()
+                    .fold(0, |value, byte| value << 8 | u64::from(*byte))
+            }
+            // Indefinite lengths and breaks.
+            _ => 0,
+        };
+        let left = (bytes.len() - position) as u64;
+        match major {
+            // Byte and text strings, whose contents are skipped.
+            2 | 3 if info < 31 => {
+                if argument > left {
+                    return Err(beyond());
+                }
+                position += argument as usize;
+            }
+            4 if argument > left => return Err(beyond()),
+            5 if argument > left / 2 => return Err(beyond()),
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// A type which is sent over the wire.
+#[cfg(not(feature = "protobuf"))]
+pub trait Wire: Serialize + DeserializeOwned {}
+
+#[cfg(not(feature = "protobuf"))]
+impl<T: Serialize + DeserializeOwned> Wire for T {}
+
+#[cfg(feature = "protobuf")]
+pub use crate::protobuf::Wire;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gossip::Gossip;
+
+    #[test]
+    fn lengths_beyond_the_input() {
+        // A gossip whose rumors claim to be more than the input could hold.
+        let mut bincode = vec![0; 32];
+        bincode.extend_from_slice(&[0xff; 8]);
+        match Codec::Bincode.decode::<Gossip>(&bincode) {
+            Err(Error::Serialisation(_)) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        #[cfg(feature = "cbor")]
+        {
+            let mut cbor = vec![0xa1, 0x66];
+            cbor.extend_from_slice(b"rumors");
+            cbor.push(0x9b);
+            cbor.extend_from_slice(&[0xff; 8]);
+            match Codec::Cbor.decode::<Gossip>(&cbor) {
+                Err(Error::Encoding(_)) => (),
+                result => panic!("Unexpected result {:?}", result),
+            }
+            // A byte string running past the end.
+            match Codec::Cbor.decode::<Gossip>(&[0x5a, 0xff, 0xff, 0xff, 0xff]) {
+                Err(Error::Encoding(_)) => (),
+                result => panic!("Unexpected result {:?}", result),
+            }
+        }
+
+        #[cfg(feature = "protobuf")]
+        {
+            let protobuf = [0x12, 0xff, 0xff, 0xff, 0xff, 0x0f];
+            match Codec::Protobuf.decode::<Gossip>(&protobuf) {
+                Err(Error::Encoding(_)) => (),
+                result => panic!("Unexpected result {:?}", result),
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use crate::error::Error;
+use std::borrow::Cow;
+
+/// The compression of transmission payloads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// Payloads are sent as they are.
+    #[default]
+    None,
+    /// Zstandard, at the default compression level.
+    #[cfg(feature = "zstd")]
+    Zstd,
+    /// LZ4 block compression, with the uncompressed size prepended.
+    #[cfg(feature = "lz4")]
+    Lz4,
+}
+
+impl Compression {
+    /// The byte identifying the compression on the wire.
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => 1,
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => 2,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> Result<Compression, Error> {
+        match id {
+            0 => Ok(Compression::None),
+            #[cfg(feature = "zstd")]
+            1 => Ok(Compression::Zstd),
+            #[cfg(feature = "lz4")]
+            2 => Ok(Compression::Lz4),
+            _ => Err(Error::UnsupportedCompression(id)),
+        }
+    }
+
+    pub(crate) fn compress(self, data: Vec<u8>) -> Result<Vec<u8>, Error> {
+        match self {
+            Compression::None => Ok(data),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(zstd::encode_all(&data[..], 0)?),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => Ok(lz4_flex::compress_prepend_size(&data)),
+        }
+    }
+
+    /// Decompresses `data`, failing with `Error::FrameTooLarge` rather than producing more
+    /// than `max_size` bytes.  Uncompressed data is returned as it is, without copying it.
+    pub(crate) fn decompress(self, data: &[u8], max_size: usize) -> Result<Cow<'_, [u8]>, Error> {
+        let decompressed = match self {
+            Compression::None => Cow::Borrowed(data),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => {
+                use std::io::Read;
+                let mut decompressed = vec![];
+                let _ = zstd::Decoder::new(data)?
+                    .take(max_size as u64 + 1)
+                    .read_to_end(&mut decompressed)?;
+                Cow::Owned(decompressed)
+            }
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => {
+                // The size is prepended as a little-endian `u32`.
+                let mut size = [0; 4];
+                size.copy_from_slice(data.get(..4).ok_or(Error::InvalidFrame)?);
+                let size = u32::from_le_bytes(size) as usize;
+                if size > max_size {
+                    return Err(Error::FrameTooLarge(size));
+                }
+                Cow::Owned(
+                    lz4_flex::decompress_size_prepended(data)
+                        .map_err(|e| Error::Encoding(e.to_string()))?,
+                )
+            }
+        };
+        if decompressed.len() > max_size {
+            return Err(Error::FrameTooLarge(decompressed.len()));
+        }
+        Ok(decompressed)
+    }
+}
+
+/// Statistics on the compression of the transmissions we sent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CompressionMetrics {
+    /// The number of payloads which were compressed.
+    pub compressed: u64,
+    /// The number of payloads which were sent uncompressed, because they were below the
+    /// threshold or compression would not have made them smaller.
+    pub skipped: u64,
+    /// The total size of the compressed payloads, before compression.
+    pub bytes_in: u64,
+    /// The total size of the compressed payloads, after compression.
+    pub bytes_out: u64,
+}
+
+impl CompressionMetrics {
+    /// The ratio of the compressed to the uncompressed size of the payloads which were
+    /// compressed, or `None` if none were.
+    pub fn ratio(&self) -> Option<f64> {
+        if self.bytes_in == 0 {
+            return None;
+        }
+        Some(self.bytes_out as f64 / self.bytes_in as f64)
+    }
+
+    pub(crate) fn record(&mut self, bytes_in: usize, bytes_out: Option<usize>) {
+        match bytes_out {
+            Some(bytes_out) => {
+                self.compressed += 1;
+                self.bytes_in += bytes_in as u64;
+                self.bytes_out += bytes_out as u64;
+            }
+            None => self.skipped += 1,
+        }
+    }
+}
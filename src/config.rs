@@ -0,0 +1,81 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use crate::codec::Codec;
+use crate::compression::Compression;
+use crate::encryption::Encryption;
+use crate::state::AgeRule;
+use std::time::Duration;
+
+/// Configuration of a `Gossiping` instance.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// How long after we first saw a rumor it is still handed out to joining players
+    /// requesting a bootstrap.
+    pub retention: Duration,
+    /// How far our clock may be ahead of the clocks of other players before we consider
+    /// their rumors to have passed their deadline.
+    pub clock_skew_tolerance: Duration,
+    /// The maximum number of rumors sent in a single gossip, or `None` for no limit.
+    pub max_rumors_per_gossip: Option<usize>,
+    /// The maximum total size in bytes of the rumors sent in a single gossip, as encoded with
+    /// `codec` before any compression, or `None` for no limit.  A single rumor exceeding it on
+    /// its own is still sent.
+    pub max_bytes_per_gossip: Option<u64>,
+    /// The encoding of the transmissions sent to the cluster.
+    pub codec: Codec,
+    /// The compression applied to the payload of the transmissions sent to the cluster.
+    pub compression: Compression,
+    /// Payloads smaller than this many bytes are sent uncompressed.
+    pub compression_threshold: usize,
+    /// The encryption of the payload of the transmissions sent to the cluster.  When set,
+    /// unencrypted transmissions from the cluster are rejected.
+    pub encryption: Encryption,
+    /// How our age for a rumor is updated from the ages other players report.  The median
+    /// rule of the paper can be thrown off by a single malicious player.
+    pub age_rule: AgeRule,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            retention: Duration::from_secs(60),
+            clock_skew_tolerance: Duration::from_secs(1),
+            max_rumors_per_gossip: None,
+            max_bytes_per_gossip: None,
+            codec: Codec::default(),
+            compression: Compression::default(),
+            compression_threshold: 512,
+            encryption: Encryption::default(),
+            age_rule: AgeRule::default(),
+        }
+    }
+}
+
+/// Limits on the transmissions received from other players, beyond which they are rejected.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// The maximum size in bytes of a frame, checked before anything else, and of its payload
+    /// once decompressed.
+    pub max_frame_size: usize,
+    /// The maximum number of rumors in a single gossip.
+    pub max_rumors_per_gossip: usize,
+    /// The maximum size in bytes of the value of a rumor.
+    pub max_content_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_frame_size: 16 * 1024 * 1024,
+            max_rumors_per_gossip: 4096,
+            max_content_size: 4 * 1024 * 1024,
+        }
+    }
+}
@@ -0,0 +1,143 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! Confidentiality of transmission payloads.
+//!
+//! Each pair of players agrees on a session key without any extra round trip: both get a
+//! shared secret from their identities (for ed25519, by converting them to X25519 keys and
+//! running a Diffie-Hellman exchange) and feed it through HKDF-SHA256, salted with both ids.
+//! Payloads are then sealed with XChaCha20-Poly1305 under a random nonce, which is sent in
+//! front of the ciphertext.
+
+use crate::error::Error;
+use crate::identity::{PublicId, SecretId};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use hkdf::Hkdf;
+use rand::RngCore;
+use sha2::Sha256;
+
+const NONCE_LENGTH: usize = 24;
+const INFO: &[u8] = b"safe_gossip session key";
+
+/// The encryption of transmission payloads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encryption {
+    /// Payloads are sent in clear.
+    #[default]
+    None,
+    /// XChaCha20-Poly1305, keyed per pair of players.
+    XChaCha20Poly1305,
+}
+
+impl Encryption {
+    /// The byte identifying the encryption on the wire.
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            Encryption::None => 0,
+            Encryption::XChaCha20Poly1305 => 1,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> Result<Encryption, Error> {
+        match id {
+            0 => Ok(Encryption::None),
+            1 => Ok(Encryption::XChaCha20Poly1305),
+            _ => Err(Error::UnsupportedEncryption(id)),
+        }
+    }
+}
+
+/// The symmetric key shared by us and one other player.
+pub(crate) struct SessionKey(XChaCha20Poly1305);
+
+impl SessionKey {
+    /// Derives the key we share with the owner of `peer`.  The owner of `peer` derives the same
+    /// key from its own secret identity and our public one.
+    pub(crate) fn derive<S: SecretId>(
+        secret_id: &S,
+        peer: &S::Public,
+    ) -> Result<SessionKey, Error> {
+        let shared = secret_id.shared_secret(peer)?;
+        let (ours, theirs) = (secret_id.public_id().id(), peer.id());
+        let (first, second) = if ours < theirs {
+            (ours, theirs)
+        } else {
+            (theirs, ours)
+        };
+        let mut salt = first.0.to_vec();
+        salt.extend_from_slice(&second.0);
+        let mut key = [0; 32];
+        Hkdf::<Sha256>::new(Some(&salt), &shared)
+            .expand(INFO, &mut key)
+            .map_err(|_| Error::KeyAgreementFailure)?;
+        Ok(SessionKey(XChaCha20Poly1305::new(&key.into())))
+    }
+
+    /// Encrypts `plaintext`, authenticating `associated_data` along with it.
+    pub(crate) fn seal(&self, plaintext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut nonce = [0; NONCE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = self
+            .0
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: associated_data,
+                },
+            )
+            .map_err(|_| Error::DecryptionFailure)?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Decrypts what `seal` produced, given the same `associated_data`.
+    pub(crate) fn open(&self, sealed: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        if sealed.len() < NONCE_LENGTH {
+            return Err(Error::DecryptionFailure);
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+        self.0
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: associated_data,
+                },
+            )
+            .map_err(|_| Error::DecryptionFailure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::Keypair;
+    use sha3::Sha3_512;
+    use unwrap::unwrap;
+
+    #[test]
+    fn session_keys_agree() {
+        let mut rng = rand::thread_rng();
+        let alice = Keypair::generate::<Sha3_512, _>(&mut rng);
+        let bob = Keypair::generate::<Sha3_512, _>(&mut rng);
+        let eve = Keypair::generate::<Sha3_512, _>(&mut rng);
+
+        let alice_key = unwrap!(SessionKey::derive(&alice, &bob.public));
+        let bob_key = unwrap!(SessionKey::derive(&bob, &alice.public));
+        let eve_key = unwrap!(SessionKey::derive(&eve, &alice.public));
+
+        let sealed = unwrap!(alice_key.seal(b"rumor", b"header"));
+        assert_eq!(unwrap!(bob_key.open(&sealed, b"header")), b"rumor");
+        assert!(bob_key.open(&sealed, b"other header").is_err());
+        assert!(eve_key.open(&sealed, b"header").is_err());
+    }
+}
@@ -7,8 +7,6 @@
 // specific language governing permissions and limitations relating to use of the SAFE Network
 // Software.
 
-use bincode;
-
 quick_error! {
     /// Gossiping error variants.
     #[derive(Debug)]
@@ -23,6 +21,104 @@ quick_error! {
             description("Already started gossiping")
             display("Connections to all other nodes must be made before sending any messages.")
         }
+        /// No gossip group with the given id.
+        UnknownGroup {
+            description("Unknown gossip group")
+            display("There is no gossip group with the given id.")
+        }
+        /// The received bytes are not a transmission.
+        InvalidFrame {
+            description("Invalid frame")
+            display("The received bytes don't start with a valid transmission header.")
+        }
+        /// The protocol version of a player is not supported.
+        IncompatibleVersion(version: u16) {
+            description("Incompatible protocol version")
+            display("Protocol version {} is not supported.", version)
+        }
+        /// The message type of a transmission is not known.
+        UnknownMessageType(kind: u8) {
+            description("Unknown message type")
+            display("Message type {} is not known.", kind)
+        }
+        /// The codec of a transmission is not supported.
+        UnsupportedCodec(codec: u8) {
+            description("Unsupported codec")
+            display("Codec {} is not supported by this build.", codec)
+        }
+        /// The payload is compressed with an algorithm not compiled into this build.
+        UnsupportedCompression(compression: u8) {
+            description("Unsupported compression")
+            display("Compression {} is not supported by this build.", compression)
+        }
+        /// The payload is encrypted with an algorithm we don't know.
+        UnsupportedEncryption(encryption: u8) {
+            description("Unsupported encryption")
+            display("Encryption {} is not supported by this build.", encryption)
+        }
+        /// Failed to agree on a session key with a player.
+        KeyAgreementFailure {
+            description("Key agreement failure")
+            display("Failed to derive a session key shared with the player.")
+        }
+        /// Failed to decrypt a payload, or to encrypt one.
+        DecryptionFailure {
+            description("Decryption failure")
+            display("Failed to decrypt the payload.")
+        }
+        /// Received a transmission which was received before, or is too old to tell.
+        Replay {
+            description("Replayed transmission")
+            display("The transmission was rejected as a possible replay.")
+        }
+        /// Received a payload in clear, in a group requiring encryption.
+        NotEncrypted {
+            description("Payload not encrypted")
+            display("Received an unencrypted payload in a group requiring encryption.")
+        }
+        /// Received a key rotation from a player of none of our groups, or whose new key is
+        /// invalid or already taken.
+        InvalidKeyRotation {
+            description("Invalid key rotation")
+            display("The key rotation is not from a known player, or its new key is unusable.")
+        }
+        /// Received a bootstrap which doesn't answer any of our requests.
+        UnsolicitedBootstrap {
+            description("Unsolicited bootstrap")
+            display("Received a bootstrap from a player we didn't ask for one.")
+        }
+        /// Received rumors in a state the protocol can't lead to.
+        ImplausibleState {
+            description("Implausible rumor state")
+            display("Received a rumor in a state the protocol can't lead to.")
+        }
+        /// The transport can't take transmissions to a player, and too many are held back for
+        /// it already.
+        WouldBlock {
+            description("Transport would block")
+            display("The transport can't take the transmission yet.")
+        }
+        /// Received a transmission whose frame, or payload once decompressed, is bigger than
+        /// the limit.
+        FrameTooLarge(size: usize) {
+            description("Frame too large")
+            display("Received a transmission of {} bytes, over the limit.", size)
+        }
+        /// Received a gossip holding more rumors than the limit.
+        TooManyRumors(count: usize) {
+            description("Too many rumors")
+            display("Received a gossip of {} rumors, over the limit.", count)
+        }
+        /// Received a rumor whose content is bigger than the limit.
+        ContentTooLarge(size: usize) {
+            description("Content too large")
+            display("Received a rumor of {} bytes, over the limit.", size)
+        }
+        /// Received a transmission on behalf of another player than the one which signed it.
+        CallerMismatch {
+            description("Caller mismatch")
+            display("The transmission names another player than the one which signed it.")
+        }
         /// Failed in verifying signature.
         SigFailure {
             description("Signature cannot be verified")
@@ -34,6 +130,11 @@ quick_error! {
             display("I/O error: {}", error)
             from()
         }
+        /// Encoding error of a codec other than bincode.
+        Encoding(reason: String) {
+            description("Encoding error")
+            display("Encoding error: {}", reason)
+        }
         /// Serialisation Error.
         Serialisation(error: Box<bincode::ErrorKind>) {
             description(error.description())
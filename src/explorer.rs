@@ -0,0 +1,227 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! Explores every way rumors can spread through a small cluster: each player may run a round,
+//! pushing to any of the players it believes oblivious, and each gossip in flight may be
+//! delivered, in any order, or lost.  Safety properties are checked in every state reached, and
+//! liveness ones in the states where nothing is left to happen.
+
+use crate::gossip::{Content, Gossip, Player};
+use crate::gossiping::Gossiping;
+use crate::id::Id;
+use std::collections::{BTreeSet, HashSet};
+
+#[derive(Clone, Debug)]
+struct InFlight {
+    from: usize,
+    to: usize,
+    is_push: bool,
+    gossip: Gossip,
+}
+
+#[derive(Clone)]
+struct Cluster {
+    players: Vec<Gossiping>,
+    // The rumors each player knows: those it initiated, and those handed to its client.
+    known: Vec<BTreeSet<Vec<u8>>>,
+    in_flight: Vec<InFlight>,
+    losses: usize,
+}
+
+impl Cluster {
+    // `rumors` are initiated by the players of the same index.
+    fn new(size: usize, rumors: &[Vec<u8>]) -> Self {
+        let ids: Vec<Id> = (0..size).map(|index| Id([index as u8; 32])).collect();
+        let mut players: Vec<Gossiping> = ids
+            .iter()
+            .map(|our_id| {
+                let others = ids.iter().filter(|id| *id != our_id);
+                Gossiping::new(*our_id, others.map(|id| Player { id: *id }).collect())
+            })
+            .collect();
+        let mut known = vec![BTreeSet::new(); size];
+        for (index, value) in rumors.iter().enumerate() {
+            unwrap!(players[index].initiate_rumor(Content::new(value.clone())));
+            let _ = known[index].insert(value.clone());
+        }
+        Self {
+            players,
+            known,
+            in_flight: vec![],
+            losses: 0,
+        }
+    }
+
+    fn fingerprint(&self) -> String {
+        let players: Vec<_> = self.players.iter().map(Gossiping::fingerprint).collect();
+        let mut in_flight: Vec<_> = self
+            .in_flight
+            .iter()
+            .map(|message| format!("{:?}", message))
+            .collect();
+        in_flight.sort();
+        format!("{:?} {:?} {}", players, in_flight, self.losses)
+    }
+
+    fn index(&self, id: Id) -> usize {
+        unwrap!(self.players.iter().position(|player| player.our_id() == id))
+    }
+
+    // Hands the new rumors of player `index` to its client, checking none is handed twice.
+    fn deliver_new_rumors(&mut self, index: usize) {
+        for content in self.players[index].take_new_rumors() {
+            assert!(
+                self.known[index].insert(content.value.to_vec()),
+                "rumor delivered twice to player {}",
+                index
+            );
+        }
+        self.players[index].check_invariants();
+    }
+
+    // The states the cluster can move to in one step.
+    fn successors(&self, max_losses: usize) -> Vec<Cluster> {
+        let mut successors = vec![];
+        for index in 0..self.players.len() {
+            if !self.players[index].has_active_rumors() {
+                continue;
+            }
+            let mut round = self.clone();
+            round.players[index].start_round();
+            let mut pushed = false;
+            for player in self.players[index].players() {
+                let mut next = round.clone();
+                if let Some(gossip) = next.players[index].gossip_to(player.id) {
+                    next.players[index].next_round();
+                    next.in_flight.push(InFlight {
+                        from: index,
+                        to: next.index(player.id),
+                        is_push: true,
+                        gossip,
+                    });
+                    successors.push(next);
+                    pushed = true;
+                }
+            }
+            if !pushed {
+                round.players[index].next_round();
+                successors.push(round);
+            }
+        }
+        for position in 0..self.in_flight.len() {
+            let mut next = self.clone();
+            let message = next.in_flight.remove(position);
+            if next.losses < max_losses {
+                let mut lost = next.clone();
+                lost.losses += 1;
+                successors.push(lost);
+            }
+            let response =
+                next.players[message.to].receive_gossip(&message.gossip, message.is_push);
+            assert!(message.is_push || response.is_none());
+            if let Some(gossip) = response {
+                assert_eq!(gossip.callee.id, next.players[message.from].our_id());
+                next.in_flight.push(InFlight {
+                    from: message.to,
+                    to: message.from,
+                    is_push: false,
+                    gossip,
+                });
+            }
+            next.deliver_new_rumors(message.to);
+            successors.push(next);
+        }
+        successors
+    }
+
+    // Checks the liveness properties once nothing is left to happen: every rumor is done with
+    // everywhere, and, unless gossips were lost, known to some player besides its initiator.
+    // That all players know it is only likely in clusters bigger than explored here.
+    fn check_final(&self, rumors: &[Vec<u8>]) {
+        assert!(self
+            .players
+            .iter()
+            .all(|player| !player.has_active_rumors()));
+        if self.losses == 0 {
+            for value in rumors {
+                let knowing = self.known.iter().filter(|known| known.contains(value));
+                assert!(knowing.count() > 1, "rumor {:?} never spread", value);
+            }
+        }
+    }
+}
+
+struct Explorer {
+    max_losses: usize,
+    max_depth: usize,
+    visited: HashSet<String>,
+    finals: usize,
+}
+
+impl Explorer {
+    // Explores all the states reachable from `cluster`, which must all lead to a final state
+    // within `max_depth` steps.
+    fn explore(&mut self, cluster: Cluster, depth: usize, rumors: &[Vec<u8>]) {
+        if !self.visited.insert(cluster.fingerprint()) {
+            return;
+        }
+        let successors = cluster.successors(self.max_losses);
+        if successors.is_empty() {
+            cluster.check_final(rumors);
+            self.finals += 1;
+            return;
+        }
+        assert!(
+            depth < self.max_depth,
+            "no final state within {} steps",
+            depth
+        );
+        for next in successors {
+            self.explore(next, depth + 1, rumors);
+        }
+    }
+}
+
+fn explore(size: usize, rumors: &[Vec<u8>], max_losses: usize) -> usize {
+    let mut explorer = Explorer {
+        max_losses,
+        max_depth: 200,
+        visited: HashSet::new(),
+        finals: 0,
+    };
+    explorer.explore(Cluster::new(size, rumors), 0, rumors);
+    assert!(explorer.finals > 0);
+    explorer.visited.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_rumor() {
+        for size in 3..=5 {
+            let _ = explore(size, &[vec![0]], 0);
+        }
+    }
+
+    #[test]
+    fn concurrent_rumors() {
+        let _ = explore(3, &[vec![0], vec![1], vec![2]], 0);
+        let _ = explore(4, &[vec![0], vec![1]], 0);
+        let _ = explore(5, &[vec![0], vec![1]], 0);
+    }
+
+    #[test]
+    fn lost_gossips() {
+        let _ = explore(3, &[vec![0], vec![1]], 2);
+        let _ = explore(4, &[vec![0]], 2);
+        let _ = explore(5, &[vec![0]], 1);
+    }
+}
@@ -0,0 +1,418 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! Entry points for the fuzz targets under `fuzz/`, which exercise types the crate doesn't
+//! export.  Only built with the `fuzzing` feature.
+
+use crate::config::{Config, Limits};
+use crate::gossip::{Content, Gossip, InformedPlayer, ObliviousPlayer, Player, Priority, Rumor};
+use crate::gossip::{Subscriptions, Topic};
+use crate::gossiping::Gossiping;
+use crate::id::Id;
+use crate::signing::NullSigner;
+use crate::state::{Age, AgeRule, Round, State};
+use crate::transmission::{Message, Transmission};
+use arbitrary::{Arbitrary, Unstructured};
+use ed25519_dalek::{PublicKey, SecretKey};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// The players other than us.  Gossips may also come from us, or from a player we don't know.
+const PLAYERS: u8 = 8;
+const TOPICS: u8 = 3;
+const OUR_ID: Id = Id([0; 32]);
+
+// The ids steps refer to by index: ours, the players' and an unknown one to begin with.
+fn initial_ids() -> Vec<Id> {
+    (0..PLAYERS + 2).map(|index| Id([index; 32])).collect()
+}
+
+fn id(ids: &[Id], index: u8) -> Id {
+    ids[usize::from(index) % ids.len()]
+}
+
+fn topic(index: u8) -> Topic {
+    Topic(format!("topic {}", index % TOPICS))
+}
+
+fn gossiping(age_rule: AgeRule) -> Gossiping {
+    let players = (1..=PLAYERS)
+        .map(|index| Player {
+            id: Id([index; 32]),
+        })
+        .collect();
+    let config = Config {
+        age_rule,
+        ..Config::default()
+    };
+    Gossiping::with_config(OUR_ID, players, config)
+}
+
+#[derive(Arbitrary, Debug)]
+enum Deadline {
+    Never,
+    Passed,
+    Later,
+    // Any point in time after the epoch, or none if too far off to be represented.
+    At { secs: u64, nanos: u32 },
+}
+
+#[derive(Arbitrary, Debug)]
+enum StateInput {
+    B {
+        round: u8,
+        age: u8,
+        player_ages: Vec<(u8, u8)>,
+    },
+    C {
+        rounds_in_state_b: u8,
+        round: u8,
+    },
+    D,
+}
+
+// Rumors share few values, so that the same rumor keeps coming back.
+#[derive(Arbitrary, Debug)]
+struct RumorInput {
+    value: u8,
+    deadline: Deadline,
+    priority: u8,
+    topic: Option<u8>,
+    state: StateInput,
+    callee: u8,
+    caller: u8,
+}
+
+#[derive(Arbitrary, Debug)]
+struct GossipInput {
+    callee: u8,
+    rumors: Vec<RumorInput>,
+    caller: u8,
+}
+
+#[derive(Arbitrary, Debug)]
+enum Step {
+    Push(GossipInput),
+    Response(GossipInput),
+    Bootstrap(GossipInput),
+    Collect,
+    Initiate(RumorInput),
+    Subscribe(u8),
+    Unsubscribe(u8),
+    Subscriptions { player: u8, topics: Vec<u8> },
+    Quarantine(u8),
+    Release(u8),
+    AddPlayer(u8),
+    RemovePlayer(u8),
+    RotatePlayer(u8),
+}
+
+fn content(input: &RumorInput) -> Content {
+    let priority = match input.priority % 3 {
+        0 => Priority::Bulk,
+        1 => Priority::Normal,
+        _ => Priority::High,
+    };
+    let deadline = match input.deadline {
+        Deadline::Never => None,
+        Deadline::Passed => Some(UNIX_EPOCH),
+        Deadline::Later => Some(SystemTime::now() + Duration::from_secs(3600)),
+        Deadline::At { secs, nanos } => Duration::from_secs(secs)
+            .checked_add(Duration::from_nanos(u64::from(nanos)))
+            .and_then(|since_epoch| UNIX_EPOCH.checked_add(since_epoch)),
+    };
+    Content {
+        value: vec![input.value % 16].into(),
+        deadline,
+        priority,
+        topic: input.topic.map(topic),
+    }
+}
+
+fn state(ids: &[Id], input: &StateInput) -> State {
+    match *input {
+        StateInput::B {
+            round,
+            age,
+            ref player_ages,
+        } => State::B {
+            round: Round::from(round),
+            age: Age::from(age),
+            player_ages: player_ages
+                .iter()
+                .map(|&(player, age)| (id(ids, player), Age::from(age)))
+                .collect(),
+        },
+        StateInput::C {
+            rounds_in_state_b,
+            round,
+        } => State::C {
+            rounds_in_state_b: Round::from(rounds_in_state_b),
+            round: Round::from(round),
+        },
+        StateInput::D => State::D,
+    }
+}
+
+fn gossip(ids: &[Id], input: &GossipInput) -> Gossip {
+    Gossip {
+        callee: ObliviousPlayer {
+            id: id(ids, input.callee),
+        },
+        rumors: input
+            .rumors
+            .iter()
+            .map(|rumor| Rumor {
+                content: content(rumor),
+                callee: ObliviousPlayer {
+                    id: id(ids, rumor.callee),
+                },
+                state: state(ids, &rumor.state),
+                caller: InformedPlayer {
+                    id: id(ids, rumor.caller),
+                },
+            })
+            .collect(),
+        caller: InformedPlayer {
+            id: id(ids, input.caller),
+        },
+    }
+}
+
+// Checks what we send in a round.
+fn check_collected(gossiping: &Gossiping, quarantined: &BTreeSet<Id>, gossip: &Gossip) {
+    assert!(!gossip.rumors.is_empty());
+    assert_eq!(gossip.caller.id, gossiping.our_id());
+    assert!(gossiping.players().contains(&Player {
+        id: gossip.callee.id
+    }));
+    assert!(!quarantined.contains(&gossip.callee.id));
+    let values: BTreeSet<_> = gossip
+        .rumors
+        .iter()
+        .map(|rumor| &rumor.content.value)
+        .collect();
+    assert_eq!(values.len(), gossip.rumors.len(), "rumor sent twice");
+}
+
+// Checks what we send back to the caller of a push.
+fn check_response(gossiping: &Gossiping, push: &Gossip, response: &Gossip) {
+    assert!(!response.rumors.is_empty());
+    assert_eq!(response.callee.id, push.caller.id);
+    assert_eq!(response.caller.id, gossiping.our_id());
+    assert!(response
+        .rumors
+        .iter()
+        .all(|rumor| rumor.caller.id == gossiping.our_id()));
+}
+
+// Receives `message` if it's a gossip, checking what we respond and the invariants after.
+fn receive(gossiping: &mut Gossiping, message: &Message) {
+    match *message {
+        Message::Push(ref push) => {
+            if let Some(response) = gossiping.receive_gossip(push, true) {
+                check_response(gossiping, push, &response);
+            }
+        }
+        Message::Response(ref response) => {
+            assert!(gossiping.receive_gossip(response, false).is_none());
+        }
+        Message::Bootstrap(ref bootstrap) => {
+            gossiping.request_bootstrap(bootstrap.caller.id);
+            let _ = gossiping.receive_bootstrap(bootstrap);
+        }
+        _ => (),
+    }
+    gossiping.check_invariants();
+}
+
+/// Parses `data` as a frame sent to us, decodes it without checking its signature, and has a
+/// `Gossiping` instance receive it if it's a gossip.
+pub fn transmission(data: &[u8]) {
+    let secret = SecretKey::from_bytes(&[1; 32]).expect("secret key of the right size");
+    let public = PublicKey::from_secret::<sha3::Sha3_512>(&secret);
+    let frames = [(public, data.to_vec())];
+    let limits = Limits::default();
+    for transmission in Transmission::deserialise_batch(&frames, &OUR_ID, &NullSigner, &limits) {
+        if let Ok(message) = transmission.and_then(|mut transmission| transmission.get_value(None))
+        {
+            receive(&mut gossiping(AgeRule::default()), &message);
+        }
+    }
+}
+
+/// Drives a `Gossiping` instance through a sequence of steps decoded from `data`, receiving
+/// arbitrary gossips, and checks the invariants of its rumors after each step, along with
+/// what it sends and delivers.
+pub fn gossiping_steps(data: &[u8]) {
+    let mut data = Unstructured::new(data);
+    let age_rule = match data.arbitrary::<Option<u8>>() {
+        Ok(Some(max_faulty)) => AgeRule::Byzantine {
+            max_faulty: usize::from(max_faulty % 4),
+        },
+        _ => AgeRule::Median,
+    };
+    let steps = Vec::<Step>::arbitrary_take_rest(data).unwrap_or_default();
+
+    let mut gossiping = gossiping(age_rule);
+    let mut ids = initial_ids();
+    let mut rotations: u64 = 0;
+    let mut quarantined = BTreeSet::new();
+    // Each rumor is to be delivered once at most, unless we initiate it again in between,
+    // which replaces what we hold of it, maybe by an expired rumor to be learned anew.
+    let mut delivered = BTreeMap::new();
+    for step in steps {
+        match step {
+            Step::Push(ref input) => receive(&mut gossiping, &Message::Push(gossip(&ids, input))),
+            Step::Response(ref input) => {
+                receive(&mut gossiping, &Message::Response(gossip(&ids, input)))
+            }
+            Step::Bootstrap(ref input) => {
+                receive(&mut gossiping, &Message::Bootstrap(gossip(&ids, input)))
+            }
+            Step::Collect => {
+                if let Some(gossip) = gossiping.collect_gossip() {
+                    check_collected(&gossiping, &quarantined, &gossip);
+                }
+            }
+            Step::Initiate(ref input) => {
+                let content = content(input);
+                let _ = delivered.remove(&content.value);
+                let _ = gossiping.initiate_rumor(content);
+            }
+            Step::Subscribe(index) => gossiping.subscribe(topic(index)),
+            Step::Unsubscribe(index) => gossiping.unsubscribe(&topic(index)),
+            Step::Subscriptions { player, topics } => {
+                gossiping.receive_subscriptions(Subscriptions {
+                    player: id(&ids, player),
+                    topics: topics.into_iter().map(topic).collect(),
+                })
+            }
+            Step::Quarantine(player) => {
+                gossiping.quarantine(id(&ids, player));
+                let _ = quarantined.insert(id(&ids, player));
+            }
+            Step::Release(player) => {
+                gossiping.release(id(&ids, player));
+                let _ = quarantined.remove(&id(&ids, player));
+            }
+            Step::AddPlayer(player) => {
+                if id(&ids, player) != gossiping.our_id() {
+                    let _ = gossiping.add_player(id(&ids, player));
+                }
+            }
+            Step::RemovePlayer(player) => {
+                gossiping.remove_player(id(&ids, player));
+                let _ = quarantined.remove(&id(&ids, player));
+            }
+            Step::RotatePlayer(player) => {
+                // Keys are rotated to fresh ones, which no rumor can refer to yet.
+                let index = usize::from(player) % ids.len();
+                let mut new = Id([0xff; 32]);
+                new.0[..8].copy_from_slice(&rotations.to_be_bytes());
+                rotations += 1;
+                let old = ids[index];
+                if gossiping.rotate_player(old, new).is_ok() {
+                    ids[index] = new;
+                    if quarantined.remove(&old) {
+                        let _ = quarantined.insert(new);
+                    }
+                }
+            }
+        }
+        gossiping.check_invariants();
+        for content in gossiping.take_new_rumors() {
+            let count = delivered.entry(content.value).or_insert(0);
+            *count += 1;
+            assert_eq!(*count, 1, "rumor delivered twice");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::CompressionMetrics;
+    use crate::id::GroupId;
+    use crate::transmission::{Envelope, PROTOCOL_VERSION};
+    use ed25519_dalek::Keypair;
+    use unwrap::unwrap;
+
+    // A few inputs no entry point may panic on, whatever it makes of them.
+    fn corpus() -> Vec<Vec<u8>> {
+        vec![
+            vec![],
+            vec![0; 64],
+            vec![0xff; 64],
+            (0..=255).collect(),
+            b"SGSP\x00\x01\x00\x00\x00\x00".to_vec(),
+        ]
+    }
+
+    // A valid frame pushing rumors to us, with the blank signature of the null signer.
+    fn push_frame() -> Vec<u8> {
+        let secret = unwrap!(SecretKey::from_bytes(&[1; 32]));
+        let public = PublicKey::from_secret::<sha3::Sha3_512>(&secret);
+        let keys = Keypair { secret, public };
+        let input = GossipInput {
+            callee: 0,
+            rumors: (0..3)
+                .map(|value| RumorInput {
+                    value,
+                    deadline: Deadline::Later,
+                    priority: value,
+                    topic: None,
+                    state: StateInput::B {
+                        round: 0,
+                        age: 1,
+                        player_ages: vec![],
+                    },
+                    callee: 0,
+                    caller: 1,
+                })
+                .collect(),
+            caller: 1,
+        };
+        let envelope = Envelope {
+            recipient: OUR_ID,
+            version: PROTOCOL_VERSION,
+            sequence: 0,
+            session: None,
+        };
+        unwrap!(Transmission::serialise(
+            &GroupId::default(),
+            &Message::Push(gossip(&initial_ids(), &input)),
+            &envelope,
+            &Config::default(),
+            &keys,
+            &NullSigner,
+            &mut CompressionMetrics::default(),
+        ))
+    }
+
+    #[test]
+    fn transmission_inputs() {
+        let frame = push_frame();
+        let mut inputs = corpus();
+        inputs.push(frame[..frame.len() / 2].to_vec());
+        inputs.push(frame);
+        for input in &inputs {
+            transmission(input);
+        }
+    }
+
+    #[test]
+    fn gossiping_steps_inputs() {
+        let mut inputs = corpus();
+        inputs.push(push_frame());
+        for input in &inputs {
+            gossiping_steps(input);
+        }
+    }
+}
@@ -9,8 +9,12 @@
 
 use crate::id::Id;
 use crate::state::State;
+use bytes::Bytes;
+use serde::{Deserialize, Deserializer, Serializer};
+use std::collections::BTreeSet;
+use std::time::{Duration, SystemTime};
 
-#[derive(Serialize, Debug, Deserialize)]
+#[derive(Serialize, Debug, Deserialize, Clone)]
 pub struct Gossip {
     pub callee: ObliviousPlayer,
     pub rumors: Vec<Rumor>,
@@ -45,6 +49,142 @@ pub struct ObliviousPlayer {
 /// The content of a rumor.
 #[derive(Clone, Serialize, Debug, Deserialize)]
 pub struct Content {
-    /// The serialized content.
-    pub value: Vec<u8>,
+    /// The serialized content, shared by all the copies of the rumor.
+    #[serde(
+        serialize_with = "serialize_value",
+        deserialize_with = "deserialize_value"
+    )]
+    pub value: Bytes,
+    /// The point in time after which the rumor is worthless, and will no longer be
+    /// propagated or delivered.  `None` means the rumor never expires.
+    pub deadline: Option<SystemTime>,
+    /// How urgently the rumor should be propagated, relative to other rumors.
+    pub priority: Priority,
+    /// The topic of the rumor.  Only players subscribed to it receive the rumor, while a rumor
+    /// without a topic is spread to all players.
+    pub topic: Option<Topic>,
+}
+
+/// The name of a logical stream of rumors within a cluster.
+#[derive(Clone, Serialize, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Topic(pub String);
+
+/// The advertisement of the topics a player is subscribed to.
+#[derive(Clone, Serialize, Debug, Deserialize)]
+pub struct Subscriptions {
+    /// The advertising player.
+    pub player: Id,
+    /// The topics the player is subscribed to.
+    pub topics: BTreeSet<Topic>,
+}
+
+/// The priority class of a rumor.  When the number or size of rumors which can be sent in a
+/// single gossip is limited, higher priority rumors are sent first.
+#[derive(Clone, Copy, Default, Serialize, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Rumors which can be deferred to later rounds.
+    Bulk,
+    /// The default priority.
+    #[default]
+    Normal,
+    /// Rumors which are sent before any others.
+    High,
+}
+
+impl Content {
+    /// Returns a new `Content` which never expires.
+    pub fn new(value: Vec<u8>) -> Self {
+        Self::from_bytes(Bytes::from(value))
+    }
+
+    /// Returns a new `Content` which never expires, sharing `value` rather than copying it.
+    pub fn from_bytes(value: Bytes) -> Self {
+        Self {
+            value,
+            deadline: None,
+            priority: Priority::default(),
+            topic: None,
+        }
+    }
+
+    /// Sets the deadline of the rumor to `ttl` from now.  A `ttl` too long to be represented
+    /// leaves the rumor without a deadline.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.deadline = SystemTime::now().checked_add(ttl);
+        self
+    }
+
+    /// Sets the priority of the rumor.
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Sets the topic of the rumor.
+    pub fn with_topic(mut self, topic: Topic) -> Self {
+        self.topic = Some(topic);
+        self
+    }
+
+    /// Returns whether the deadline has passed, allowing for our clock to be ahead of the
+    /// originator's by up to `clock_skew_tolerance`.  A deadline too far in the future to add
+    /// the tolerance to is never passed.
+    pub fn is_expired(&self, clock_skew_tolerance: Duration) -> bool {
+        self.deadline
+            .and_then(|deadline| deadline.checked_add(clock_skew_tolerance))
+            .is_some_and(|deadline| SystemTime::now() > deadline)
+    }
+}
+
+// The value is encoded as a sequence of bytes, as it was when held in a `Vec<u8>`, so that the
+// encoding of rumors is unchanged.
+fn serialize_value<S: Serializer>(value: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(value.iter())
+}
+
+fn deserialize_value<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+    Vec::<u8>::deserialize(deserializer).map(Bytes::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn content_encoding_is_unchanged() {
+        let content = Content::new(vec![1, 2, 3]).with_priority(Priority::High);
+        let as_vec = (
+            vec![1u8, 2, 3],
+            None::<SystemTime>,
+            Priority::High,
+            None::<Topic>,
+        );
+        let encoded = unwrap!(bincode::serialize(&content));
+        assert_eq!(encoded, unwrap!(bincode::serialize(&as_vec)));
+
+        let decoded: Content = unwrap!(bincode::deserialize(&encoded));
+        assert_eq!(decoded.value, Bytes::from(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn extreme_deadlines() {
+        let tolerance = Duration::from_secs(1);
+        let far = unwrap!(UNIX_EPOCH.checked_add(Duration::from_secs(i64::MAX as u64)));
+        let content = Content {
+            deadline: Some(far),
+            ..Content::new(vec![1])
+        };
+        let decoded: Content =
+            unwrap!(bincode::deserialize(&unwrap!(bincode::serialize(&content))));
+        assert_eq!(decoded.deadline, Some(far));
+        assert!(!decoded.is_expired(tolerance));
+
+        assert!(Content::new(vec![1])
+            .with_ttl(Duration::from_secs(u64::MAX))
+            .deadline
+            .is_none());
+        let content = Content::new(vec![1]).with_ttl(Duration::from_secs(60));
+        assert!(!content.is_expired(tolerance));
+    }
 }
@@ -7,29 +7,68 @@
 // specific language governing permissions and limitations relating to use of the SAFE Network
 // Software.
 
+use crate::compression::CompressionMetrics;
+use crate::config::{Config, Limits};
+use crate::encryption::{Encryption, SessionKey};
 use crate::error::Error;
-use crate::gossip::Content;
+use crate::gossip::{Content, Player, Topic};
 use crate::gossiping::Gossiping;
-use crate::id::Id;
-use crate::transmission::Transmission;
-use ed25519_dalek::Keypair;
-use ed25519_dalek::PublicKey;
+use crate::id::{GroupId, Id};
+use crate::identity::{PublicId, SecretId};
+use crate::rate_limit::{RateLimitConfig, RateLimiter};
+use crate::replay::{self, ReplayWindow};
+use crate::reputation::{Misbehaviour, QuarantineEvent, Reputation, ReputationConfig};
+use crate::signing::{NativeSigner, Signer, Verifier};
+use crate::transmission::{
+    Envelope, KeyRotation, Message, Transmission, Versions, PROTOCOL_VERSION,
+};
+use ed25519_dalek::{Keypair, PublicKey};
 use futures::{Async, Future, Poll};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+// The most transmissions held back per player while the transport can't take them.
+const MAX_DEFERRED: usize = 256;
+
+// Transmissions held back, with the public identity they are sent with.
+type Deferred<P> = VecDeque<(P, Vec<u8>)>;
 
 /// Defines the communication interface between
 /// players in this gossip protocol.
 /// Should allow for implementation of any transport protocol.
-pub trait PlayerIncomingChannel {
+/// `P` is the public identity of the players, as authenticated by the transport.
+pub trait PlayerIncomingChannel<P = PublicKey> {
     /// Receives rumors from other players.
-    fn receive_from_players(&mut self) -> Vec<(PublicKey, Vec<u8>)>;
+    fn receive_from_players(&mut self) -> Vec<(P, Vec<u8>)>;
+
+    /// Tells the transport that the player `old` rotated its key to `new`, so that its
+    /// transmissions are from now on verified with `new`.
+    fn rotate_key(&mut self, _old: Id, _new: &P) {}
 }
 
 /// Defines the communication interface between
 /// players in this gossip protocol.
 /// Should allow for implementation of any transport protocol.
-pub trait PlayerOutgoingChannels {
-    /// Sends rumors to other player,
-    fn send_to_player(&mut self, id: Id, transmission: (PublicKey, Vec<u8>)) -> Result<(), Error>;
+/// `P` is the public identity of the players, as authenticated by the transport.
+pub trait PlayerOutgoingChannels<P = PublicKey> {
+    /// Sends rumors to other player.  If the transport can't take the transmission yet, it's
+    /// handed back in `SendError::WouldBlock`, and sent again later.
+    fn send_to_player(&mut self, id: Id, transmission: (P, Vec<u8>)) -> Result<(), SendError<P>>;
+}
+
+/// The failure of `PlayerOutgoingChannels` to send a transmission.
+#[derive(Debug)]
+pub enum SendError<P = PublicKey> {
+    /// The transport can't take the transmission yet, and hands it back.
+    WouldBlock((P, Vec<u8>)),
+    /// The transmission can't be sent.
+    Failed(Error),
+}
+
+impl<P> From<Error> for SendError<P> {
+    fn from(error: Error) -> Self {
+        SendError::Failed(error)
+    }
 }
 
 /// Defines the communication interface between
@@ -38,24 +77,33 @@ pub trait PlayerOutgoingChannels {
 pub trait ClientChannel {
     /// Reads any input from user.
     fn read_from_client(&mut self) -> Option<ClientCmd>;
+    /// Hands a rumor received from other players of the given group to the user.
+    fn write_to_client(&mut self, group: GroupId, content: Content);
+    /// Tells the user that a player was quarantined for misbehaving, or released.
+    fn report_quarantine(&mut self, _event: QuarantineEvent) {}
 }
 
 /// A cmd sent by the
 /// user of this protocol.
 pub enum ClientCmd {
-    /// Starts a new rumor.
-    NewRumor(Content),
+    /// Starts a new rumor in the given group.
+    NewRumor(GroupId, Content),
+    /// Subscribes to the rumors of a topic in the given group.
+    Subscribe(GroupId, Topic),
+    /// Unsubscribes from the rumors of a topic in the given group.
+    Unsubscribe(GroupId, Topic),
     /// Shuts down this instance.
     Shutdown,
 }
 
 // todo: quic-p2p impl
 
-impl<C, I, O> Future for GossipStepper<C, I, O>
+impl<C, I, O, S> Future for GossipStepper<C, I, O, S>
 where
     C: ClientChannel,
-    I: PlayerIncomingChannel,
-    O: PlayerOutgoingChannels,
+    I: PlayerIncomingChannel<S::Public>,
+    O: PlayerOutgoingChannels<S::Public>,
+    S: SecretId,
 {
     type Item = ();
     type Error = Error;
@@ -71,6 +119,10 @@ where
         if self.receive_from_players().is_err() {
             println!("self.receive_from_players() is_err!");
         }
+        self.release_quarantined();
+        if let Err(error) = self.send_deferred() {
+            warn!("Failed to send the transmissions held back: {}", error);
+        }
         if self.try_send_gossip().is_err() {
             println!("self.try_send_gossip() is_err!");
         }
@@ -80,9 +132,34 @@ where
 }
 
 /// Used to carry out gossiping.
-pub struct GossipStepper<C, I, O> {
-    keys: Keypair,
-    gossiping: Gossiping,
+/// A single stepper can take part in several gossip groups, all of them sharing the same
+/// identity and transport.  `S` is the secret identity of this node, ed25519 by default.
+pub struct GossipStepper<C, I, O, S: SecretId = Keypair> {
+    secret_id: S,
+    signer: Box<dyn Signer<S>>,
+    verifier: Box<dyn Verifier<S::Public>>,
+    groups: BTreeMap<GroupId, Gossiping>,
+    // The public identities of the players we know of.
+    public_ids: BTreeMap<Id, S::Public>,
+    // The protocol version negotiated with each player.
+    versions: BTreeMap<Id, u16>,
+    // The players we sent a `Hello` to.
+    greeted: BTreeSet<Id>,
+    // The keys shared with each player we exchanged encrypted transmissions with.
+    sessions: BTreeMap<Id, SessionKey>,
+    // The sequence number of the next transmission we send to each player.  Each player has
+    // its own, so that its replay window only tracks the transmissions sent to it.
+    next_sequences: BTreeMap<Id, u64>,
+    // The sequence numbers received from each player.
+    replay_windows: BTreeMap<Id, ReplayWindow>,
+    // The scores of the players which misbehaved.
+    reputation: Reputation,
+    rate_limiter: RateLimiter<S::Public>,
+    // The limits on the transmissions received.
+    limits: Limits,
+    // The transmissions to each player the transport couldn't take yet, in order.
+    deferred: BTreeMap<Id, Deferred<S::Public>>,
+    compression_metrics: CompressionMetrics,
     client: C,
     listener: I,
     player_channels: O,
@@ -93,26 +170,66 @@ pub struct GossipStepper<C, I, O> {
     _p_o: std::marker::PhantomData<O>,
 }
 
-impl<C, I, O> GossipStepper<C, I, O>
+impl<C, I, O, S> GossipStepper<C, I, O, S>
 where
     C: ClientChannel,
-    I: PlayerIncomingChannel,
-    O: PlayerOutgoingChannels,
+    I: PlayerIncomingChannel<S::Public>,
+    O: PlayerOutgoingChannels<S::Public>,
+    S: SecretId,
 {
-    /// Constructor
+    /// Constructor.  The given `gossiping` instance becomes the default group, with an id of
+    /// `GroupId::default()`.  Transmissions are signed and verified with the signature scheme
+    /// of the identities.
     pub fn new(
-        keys: Keypair,
+        secret_id: S,
         gossiping: Gossiping,
         client: C,
         listener: I,
         player_channels: O,
     ) -> Self {
-        Self {
-            keys,
+        Self::with_signing(
+            secret_id,
             gossiping,
             client,
             listener,
             player_channels,
+            Box::new(NativeSigner),
+            Box::new(NativeSigner),
+        )
+    }
+
+    /// Returns a new instance signing transmissions with `signer` and verifying those received
+    /// with `verifier`.
+    pub fn with_signing(
+        secret_id: S,
+        gossiping: Gossiping,
+        client: C,
+        listener: I,
+        player_channels: O,
+        signer: Box<dyn Signer<S>>,
+        verifier: Box<dyn Verifier<S::Public>>,
+    ) -> Self {
+        let mut groups = BTreeMap::new();
+        let _ = groups.insert(GroupId::default(), gossiping);
+        Self {
+            secret_id,
+            signer,
+            verifier,
+            groups,
+            public_ids: BTreeMap::new(),
+            versions: BTreeMap::new(),
+            greeted: BTreeSet::new(),
+            sessions: BTreeMap::new(),
+            next_sequences: BTreeMap::new(),
+            replay_windows: BTreeMap::new(),
+            reputation: Reputation::default(),
+            rate_limiter: RateLimiter::default(),
+            limits: Limits::default(),
+            deferred: BTreeMap::new(),
+            compression_metrics: CompressionMetrics::default(),
+            client,
+            listener,
+            player_channels,
             is_processing: false,
             is_aborted: false,
             _p_c: std::marker::PhantomData,
@@ -121,22 +238,141 @@ where
         }
     }
 
+    /// Scores misbehaving players as per `config` rather than the default one.
+    pub fn with_reputation(mut self, config: ReputationConfig) -> Self {
+        self.reputation = Reputation::new(config);
+        self
+    }
+
+    /// Limits the transmissions handled from each player as per `config`.  There are no limits
+    /// by default.
+    pub fn with_rate_limit(mut self, config: RateLimitConfig) -> Self {
+        self.rate_limiter = RateLimiter::new(config);
+        self
+    }
+
+    /// Rejects transmissions beyond `limits` rather than the default ones.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     /// Returns the Id of this instance.
     pub fn our_id(&mut self) -> Id {
-        self.gossiping.our_id()
+        self.secret_id.public_id().id()
+    }
+
+    /// Returns how well the payloads we sent so far compressed.
+    pub fn compression_metrics(&self) -> &CompressionMetrics {
+        &self.compression_metrics
+    }
+
+    /// Returns the current score of a player, which gets quarantined when it drops to zero.
+    pub fn score(&self, player: &Id) -> u32 {
+        self.reputation.score(player)
     }
 
-    /// Adds a player to the gossip cluster.
-    pub fn add_player(&mut self, public_key: PublicKey, channels: O) -> Result<(), Error> {
-        let id = Id::from(public_key);
-        self.gossiping.add_player(id)?;
+    /// Returns the number of transmissions dropped for exceeding the rate limits.
+    pub fn rate_limited(&self) -> u64 {
+        self.rate_limiter.dropped()
+    }
+
+    /// Adds a gossip group, returning the instance previously held for `group`, if any.
+    /// The `gossiping` instance is expected to have our Id.
+    pub fn add_group(&mut self, group: GroupId, gossiping: Gossiping) -> Option<Gossiping> {
+        self.groups.insert(group, gossiping)
+    }
+
+    /// Removes a gossip group, returning its instance.
+    pub fn remove_group(&mut self, group: &GroupId) -> Option<Gossiping> {
+        self.groups.remove(group)
+    }
+
+    /// Adds a player to the given gossip group.
+    pub fn add_player(
+        &mut self,
+        group: &GroupId,
+        public_id: S::Public,
+        channels: O,
+    ) -> Result<(), Error> {
+        let id = public_id.id();
+        let _ = self.public_ids.insert(id, public_id);
+        let is_quarantined = self.reputation.is_quarantined(&id);
+        let gossiping = self.group_mut(group)?;
+        gossiping.add_player(id)?;
+        if is_quarantined {
+            gossiping.quarantine(id);
+        }
+        // Let the new player know which of our rumors it should send us.
+        let subscriptions = Message::Subscriptions(gossiping.subscriptions());
         // todo: don't discard result
         self.player_channels = channels;
+        self.send(group, id, &subscriptions)
+    }
+
+    /// Asks the given players of a group for the rumors they have recently seen.
+    /// Used by a newly joined player to catch up with rumors it would otherwise never learn.
+    pub fn request_bootstrap(
+        &mut self,
+        group: &GroupId,
+        players: &[S::Public],
+    ) -> Result<(), Error> {
+        let request = Message::BootstrapRequest(self.group_mut(group)?.our_id());
+        for public_id in players {
+            let id = public_id.id();
+            let _ = self.public_ids.insert(id, public_id.clone());
+            self.group_mut(group)?.request_bootstrap(id);
+            self.send(group, id, &request)?;
+        }
         Ok(())
     }
 
+    /// Replaces our key by `secret_id`.  Every player we share a group
+    /// with is sent the new public identity, signed with the old key and vouched for by the new
+    /// one, and keeps our place in the ongoing rumors.  If any player can't be told, the first
+    /// error is returned once the key was replaced anyway.
+    pub fn rotate_key(&mut self, secret_id: S) -> Result<(), Error> {
+        let old = self.our_id();
+        let public_id = secret_id.public_id();
+        let proof = KeyRotation::proof(&old, &public_id.id())?;
+        let rotation = Message::KeyRotation(KeyRotation {
+            public_id: public_id.to_bytes(),
+            sig: self.signer.sign(&secret_id, &proof)?,
+        });
+        // Each player is told once, even if it's in several of our groups.
+        let mut told = BTreeSet::new();
+        let recipients: Vec<(GroupId, Id)> = self
+            .groups
+            .iter()
+            .flat_map(|(group, gossiping)| {
+                gossiping
+                    .players()
+                    .iter()
+                    .map(move |player| (group.clone(), player.id))
+            })
+            .filter(|(_, id)| told.insert(*id))
+            .collect();
+        let mut result = Ok(());
+        for (group, id) in recipients {
+            let sent = self.send(&group, id, &rotation);
+            if result.is_ok() {
+                result = sent;
+            }
+        }
+        for gossiping in self.groups.values_mut() {
+            let rotated = gossiping.rotate_player(old, public_id.id());
+            if result.is_ok() {
+                result = rotated;
+            }
+        }
+        // The session keys were derived from the old key.
+        self.sessions.clear();
+        self.secret_id = secret_id;
+        result
+    }
+
     /// Removes a player from the gossip cluster.
-    pub fn remove_player(&mut self, _public_key: PublicKey) {
+    pub fn remove_player(&mut self, _public_id: S::Public) {
         // todo
     }
 
@@ -144,50 +380,1190 @@ where
         self.is_aborted
     }
 
+    fn group_mut(&mut self, group: &GroupId) -> Result<&mut Gossiping, Error> {
+        self.groups.get_mut(group).ok_or(Error::UnknownGroup)
+    }
+
+    /// Sends `message` to a player, encoded as per the config of the group and using the protocol
+    /// version negotiated with the player.  The first transmission to a player, whether it was
+    /// given at construction, added since or only heard from, is preceded by a `Hello`, and our
+    /// newest version is used until the player answers it.
+    fn send(&mut self, group: &GroupId, id: Id, message: &Message) -> Result<(), Error> {
+        if !self.versions.contains_key(&id) && self.greeted.insert(id) {
+            self.transmit(group, id, &Message::Hello(Versions::ours()))?;
+        }
+        self.transmit(group, id, message)
+    }
+
+    /// Serialises `message` and hands it to the transport.
+    fn transmit(&mut self, group: &GroupId, id: Id, message: &Message) -> Result<(), Error> {
+        let default_config = Config::default();
+        let config = self
+            .groups
+            .get(group)
+            .map_or(&default_config, |gossiping| gossiping.config());
+        let encrypted = config.encryption != Encryption::None;
+        let version = self.versions.get(&id).copied().unwrap_or(PROTOCOL_VERSION);
+        let session = if encrypted {
+            Some(session_key(
+                &mut self.sessions,
+                &self.secret_id,
+                &self.public_ids,
+                id,
+            )?)
+        } else {
+            None
+        };
+        let sequence = self
+            .next_sequences
+            .entry(id)
+            .or_insert_with(replay::first_sequence);
+        let envelope = Envelope {
+            recipient: id,
+            version,
+            sequence: *sequence,
+            session,
+        };
+        *sequence += 1;
+        let result = Transmission::serialise(
+            group,
+            message,
+            &envelope,
+            config,
+            &self.secret_id,
+            &*self.signer,
+            &mut self.compression_metrics,
+        );
+        self.deliver(id, (self.secret_id.public_id(), result?))
+    }
+
+    /// Hands a transmission to the transport, or holds it back behind those the transport
+    /// couldn't take yet.  Only if too many are held back already is `Error::WouldBlock`
+    /// returned.
+    fn deliver(&mut self, id: Id, transmission: (S::Public, Vec<u8>)) -> Result<(), Error> {
+        let deferred = self.deferred.entry(id).or_default();
+        if !deferred.is_empty() {
+            if deferred.len() >= MAX_DEFERRED {
+                return Err(Error::WouldBlock);
+            }
+            deferred.push_back(transmission);
+            return Ok(());
+        }
+        match self.player_channels.send_to_player(id, transmission) {
+            Ok(()) => Ok(()),
+            Err(SendError::WouldBlock(transmission)) => {
+                deferred.push_back(transmission);
+                Ok(())
+            }
+            Err(SendError::Failed(error)) => Err(error),
+        }
+    }
+
+    /// Sends the transmissions held back, for as long as the transport takes them.
+    fn send_deferred(&mut self) -> Result<(), Error> {
+        let mut result = Ok(());
+        for (id, deferred) in &mut self.deferred {
+            while let Some(transmission) = deferred.pop_front() {
+                match self.player_channels.send_to_player(*id, transmission) {
+                    Ok(()) => (),
+                    Err(SendError::WouldBlock(transmission)) => {
+                        deferred.push_front(transmission);
+                        break;
+                    }
+                    Err(SendError::Failed(error)) => {
+                        if result.is_ok() {
+                            result = Err(error);
+                        }
+                    }
+                }
+            }
+        }
+        self.deferred.retain(|_, deferred| !deferred.is_empty());
+        result
+    }
+
     fn read_from_client(&mut self) -> Result<(), Error> {
         if let Some(cmd) = self.client.read_from_client() {
             match cmd {
-                ClientCmd::NewRumor(content) => self.gossiping.initiate_rumor(content)?,
+                ClientCmd::NewRumor(group, content) => {
+                    self.group_mut(&group)?.initiate_rumor(content)?
+                }
+                ClientCmd::Subscribe(group, topic) => {
+                    self.group_mut(&group)?.subscribe(topic);
+                    self.advertise_subscriptions(&group)?
+                }
+                ClientCmd::Unsubscribe(group, topic) => {
+                    self.group_mut(&group)?.unsubscribe(&topic);
+                    self.advertise_subscriptions(&group)?
+                }
                 ClientCmd::Shutdown => self.is_aborted = true,
             }
         }
         Ok(())
     }
 
+    /// Sends the topics we are subscribed to, to all players of the group.
+    fn advertise_subscriptions(&mut self, group: &GroupId) -> Result<(), Error> {
+        let gossiping = self.group_mut(group)?;
+        let subscriptions = Message::Subscriptions(gossiping.subscriptions());
+        let players: Vec<Id> = gossiping.players().iter().map(|p| p.id).collect();
+        for id in players {
+            self.send(group, id, &subscriptions)?;
+        }
+        Ok(())
+    }
+
     /// Iterate the players reading any new messages from them.
+    /// Their signatures are verified as one batch.  A bad transmission doesn't keep us from
+    /// handling the others, and the first error is returned once all were handled.  Its
+    /// sender is penalised if the error is down to it, which can only be told once the
+    /// signature verified.
     fn receive_from_players(&mut self) -> Result<(), Error> {
-        let mut has_response = false;
-        for (public_key, bytes) in self.listener.receive_from_players() {
-            has_response = true;
-            let mut transmission = Transmission::deserialise(&bytes[..], &public_key)?;
-            let (gossip, is_push) = transmission.get_value()?;
-            if let Some(response) = self.gossiping.receive_gossip(&gossip, is_push) {
-                let result = Transmission::serialise(&response, false, &self.keys); // Id::from(public_key)
-                self.player_channels
-                    .send_to_player(response.callee.id, (self.keys.public, result?))?
-            } else if is_push {
-                println!("No gossip collected in receive_from_players().")
-            } else {
-                println!("Response received.")
-            }
-        }
-        self.is_processing = has_response;
+        let incoming = self
+            .rate_limiter
+            .admit(self.listener.receive_from_players());
+        self.is_processing = !incoming.is_empty();
+        let our_id = self.secret_id.public_id().id();
+        let transmissions =
+            Transmission::deserialise_batch(&incoming, &our_id, &*self.verifier, &self.limits);
+        let mut result = Ok(());
+        for ((public_id, _), transmission) in incoming.iter().zip(transmissions) {
+            let received = transmission.and_then(|transmission| {
+                let received = self.receive(public_id.clone(), transmission);
+                // Only the handling of the transmission is down to the sender, not our reply.
+                if let Some(misbehaviour) = received.as_ref().err().and_then(Misbehaviour::of) {
+                    self.penalise(public_id.id(), misbehaviour);
+                }
+                received
+            });
+            let replied = received.and_then(|reply| match reply {
+                Some((group, id, reply)) => self.send(&group, id, &reply),
+                None => Ok(()),
+            });
+            if result.is_ok() {
+                result = replied;
+            }
+        }
+        result
+    }
+
+    /// Handles a transmission from the owner of `public_id`, whose signature was verified.
+    /// Returns the reply to send, if any, and to whom.
+    fn receive(
+        &mut self,
+        public_id: S::Public,
+        mut transmission: Transmission,
+    ) -> Result<Option<(GroupId, Id, Message)>, Error> {
+        let sender = public_id.id();
+        let _ = self.public_ids.insert(sender, public_id);
+        self.replay_windows
+            .entry(sender)
+            .or_default()
+            .receive(transmission.sequence())?;
+        let group = transmission.group().clone();
+        let encryption = transmission.encryption();
+        let required = self
+            .groups
+            .get(&group)
+            .map_or(Encryption::None, |gossiping| gossiping.config().encryption);
+        if encryption == Encryption::None && required != Encryption::None {
+            return Err(Error::NotEncrypted);
+        }
+        let session = if encryption == Encryption::None {
+            None
+        } else {
+            Some(session_key(
+                &mut self.sessions,
+                &self.secret_id,
+                &self.public_ids,
+                sender,
+            )?)
+        };
+        let message = match transmission.get_value(session)? {
+            Message::Hello(versions) => {
+                return self.receive_hello(&group, sender, versions);
+            }
+            Message::KeyRotation(rotation) => {
+                return self.receive_key_rotation(sender, &rotation).map(|()| None);
+            }
+            // The ages of the rumors are credited to the caller, so it must be the sender.
+            Message::Push(gossip) | Message::Response(gossip) | Message::Bootstrap(gossip)
+                if gossip.caller.id != sender =>
+            {
+                return Err(Error::CallerMismatch);
+            }
+            message => message,
+        };
+        let gossiping = self.group_mut(&group)?;
+        if let Message::Push(gossip) | Message::Response(gossip) | Message::Bootstrap(gossip) =
+            &message
+        {
+            if !gossiping.is_plausible(gossip) {
+                return Err(Error::ImplausibleState);
+            }
+        }
+        let reply = match message {
+            Message::Push(gossip) => {
+                let response = gossiping.receive_gossip(&gossip, true);
+                if response.is_none() {
+                    println!("No gossip collected in receive_from_players().")
+                }
+                response.map(|response| (sender, Message::Response(response)))
+            }
+            Message::Response(gossip) => {
+                let _ = gossiping.receive_gossip(&gossip, false);
+                println!("Response received.");
+                None
+            }
+            Message::BootstrapRequest(player_id) => {
+                if sender != player_id {
+                    return Err(Error::CallerMismatch);
+                }
+                gossiping
+                    .receive_bootstrap_request(player_id)
+                    .map(|bootstrap| (player_id, Message::Bootstrap(bootstrap)))
+            }
+            Message::Bootstrap(gossip) => {
+                gossiping.receive_bootstrap(&gossip)?;
+                None
+            }
+            Message::Subscriptions(subscriptions) => {
+                if sender != subscriptions.player {
+                    return Err(Error::CallerMismatch);
+                }
+                gossiping.receive_subscriptions(subscriptions);
+                None
+            }
+            Message::Hello(_) | Message::KeyRotation(_) => None,
+        };
+        for content in gossiping.take_new_rumors() {
+            self.client.write_to_client(group.clone(), content);
+        }
+        Ok(reply.map(|(id, reply)| (group, id, reply)))
+    }
+
+    /// Settles on the protocol version to use with a player, answering with the versions we
+    /// understand unless we sent them already.
+    fn receive_hello(
+        &mut self,
+        group: &GroupId,
+        id: Id,
+        versions: Versions,
+    ) -> Result<Option<(GroupId, Id, Message)>, Error> {
+        let _ = self.versions.insert(id, versions.negotiate()?);
+        if !self.greeted.insert(id) {
+            return Ok(None);
+        }
+        Ok(Some((group.clone(), id, Message::Hello(Versions::ours()))))
+    }
+
+    /// Moves everything we know of the player `old` to the new identity it announced, in all
+    /// groups.  The transmission was signed with the old key, so only `old` itself can do so,
+    /// and the rotation with the new one, so that it can't claim a key it doesn't hold.
+    fn receive_key_rotation(&mut self, old: Id, rotation: &KeyRotation) -> Result<(), Error> {
+        let public_id = S::Public::from_bytes(&rotation.public_id)?;
+        let new = public_id.id();
+        let is_player = |gossiping: &Gossiping| gossiping.players().contains(&Player { id: old });
+        if !self.groups.values().any(is_player) {
+            return Err(Error::InvalidKeyRotation);
+        }
+        let proof = KeyRotation::proof(&old, &new)?;
+        self.verifier.verify(&public_id, &proof, &rotation.sig)?;
+        // Every group is checked before any is changed, so that none is left with the old id.
+        if self.public_ids.contains_key(&new)
+            || self
+                .groups
+                .values()
+                .any(|gossiping| is_player(gossiping) && !gossiping.can_rotate_player(old, new))
+        {
+            return Err(Error::InvalidKeyRotation);
+        }
+        for gossiping in self.groups.values_mut() {
+            if is_player(gossiping) {
+                gossiping.rotate_player(old, new)?;
+            }
+        }
+        let _ = self.public_ids.remove(&old);
+        let _ = self.public_ids.insert(new, public_id.clone());
+        let _ = self.sessions.remove(&old);
+        if let Some(version) = self.versions.remove(&old) {
+            let _ = self.versions.insert(new, version);
+        }
+        if self.greeted.remove(&old) {
+            let _ = self.greeted.insert(new);
+        }
+        // The player carries on with the same sequence numbers, and so do we.
+        if let Some(window) = self.replay_windows.remove(&old) {
+            let _ = self.replay_windows.insert(new, window);
+        }
+        if let Some(sequence) = self.next_sequences.remove(&old) {
+            let _ = self.next_sequences.insert(new, sequence);
+        }
+        self.reputation.rotate(old, new);
+        self.listener.rotate_key(old, &public_id);
         Ok(())
     }
 
-    /// Tries to trigger a new push round.
+    /// Lowers the score of a player, quarantining it in all groups if it drops to zero.
+    fn penalise(&mut self, id: Id, misbehaviour: Misbehaviour) {
+        if self.reputation.penalise(id, misbehaviour) {
+            for gossiping in self.groups.values_mut() {
+                gossiping.quarantine(id);
+            }
+            self.client
+                .report_quarantine(QuarantineEvent::Quarantined(id));
+        }
+    }
+
+    /// Lets the players whose quarantine is over back into the push rounds.
+    fn release_quarantined(&mut self) {
+        for id in self.reputation.release_expired() {
+            for gossiping in self.groups.values_mut() {
+                gossiping.release(id);
+            }
+            self.client.report_quarantine(QuarantineEvent::Released(id));
+        }
+    }
+
+    /// Tries to trigger a new push round in each group.
     fn try_send_gossip(&mut self) -> Result<(), Error> {
         if self.is_processing {
             return Ok(());
         }
-        if let Some(gossip) = self.gossiping.collect_gossip() {
-            self.is_processing = true;
-            let result = Transmission::serialise(&gossip, true, &self.keys);
-            self.player_channels
-                .send_to_player(gossip.callee.id, (self.keys.public, result?))?;
-        } else {
+        let pushes: Vec<(GroupId, Id, Message)> = self
+            .groups
+            .iter_mut()
+            .filter_map(|(group, gossiping)| {
+                let gossip = gossiping.collect_gossip()?;
+                Some((group.clone(), gossip.callee.id, Message::Push(gossip)))
+            })
+            .collect();
+        if pushes.is_empty() {
             println!("No gossip collected in try_send_gossip().")
         }
+        for (group, callee, push) in pushes {
+            self.is_processing = true;
+            self.send(&group, callee, &push)?;
+        }
         Ok(())
     }
 }
+
+/// Returns the key shared with the player `id`, deriving it on first use.  This requires the
+/// public identity of the player, which we learn when it's added or when we hear from it.
+fn session_key<'a, S: SecretId>(
+    sessions: &'a mut BTreeMap<Id, SessionKey>,
+    secret_id: &S,
+    public_ids: &BTreeMap<Id, S::Public>,
+    id: Id,
+) -> Result<&'a SessionKey, Error> {
+    match sessions.entry(id) {
+        Entry::Occupied(entry) => Ok(entry.into_mut()),
+        Entry::Vacant(entry) => {
+            let public_id = public_ids.get(&id).ok_or(Error::KeyAgreementFailure)?;
+            Ok(entry.insert(SessionKey::derive(secret_id, public_id)?))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gossip::{Gossip, InformedPlayer, ObliviousPlayer, Rumor, Subscriptions};
+    use crate::rate_limit::Overflow;
+    use crate::state::{Round, State};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+    use unwrap::unwrap;
+
+    type Frame = (PublicKey, Vec<u8>);
+
+    // The transmissions in flight to each player, over a transport shared by all steppers.
+    #[derive(Default)]
+    struct Network {
+        inboxes: BTreeMap<Id, VecDeque<Frame>>,
+        // The players the transport can't take transmissions to for now.
+        blocked: BTreeSet<Id>,
+        // The players the transport fails to send to.
+        broken: BTreeSet<Id>,
+    }
+
+    #[derive(Clone, Default)]
+    struct Channels(Rc<RefCell<Network>>);
+
+    impl PlayerOutgoingChannels for Channels {
+        fn send_to_player(&mut self, id: Id, frame: Frame) -> Result<(), SendError> {
+            let mut network = self.0.borrow_mut();
+            if network.blocked.contains(&id) {
+                return Err(SendError::WouldBlock(frame));
+            }
+            if network.broken.contains(&id) {
+                return Err(Error::Encoding("broken transport".to_string()).into());
+            }
+            network.inboxes.entry(id).or_default().push_back(frame);
+            Ok(())
+        }
+    }
+
+    struct Listener {
+        id: Id,
+        network: Channels,
+    }
+
+    impl PlayerIncomingChannel for Listener {
+        fn receive_from_players(&mut self) -> Vec<Frame> {
+            let mut network = self.network.0.borrow_mut();
+            network
+                .inboxes
+                .remove(&self.id)
+                .map(Vec::from)
+                .unwrap_or_default()
+        }
+    }
+
+    #[derive(Default)]
+    struct Client {
+        commands: VecDeque<ClientCmd>,
+        rumors: Vec<(GroupId, Content)>,
+        quarantines: Vec<QuarantineEvent>,
+    }
+
+    impl ClientChannel for Client {
+        fn read_from_client(&mut self) -> Option<ClientCmd> {
+            self.commands.pop_front()
+        }
+
+        fn write_to_client(&mut self, group: GroupId, content: Content) {
+            self.rumors.push((group, content));
+        }
+
+        fn report_quarantine(&mut self, event: QuarantineEvent) {
+            self.quarantines.push(event);
+        }
+    }
+
+    type Stepper = GossipStepper<Client, Listener, Channels>;
+
+    fn stepper(network: &Channels, config: &Config) -> Stepper {
+        let keys = Keypair::generate::<sha3::Sha3_512, _>(&mut rand::thread_rng());
+        let id = keys.public.id();
+        let gossiping = Gossiping::with_config(id, BTreeSet::new(), config.clone());
+        let listener = Listener {
+            id,
+            network: network.clone(),
+        };
+        GossipStepper::new(
+            keys,
+            gossiping,
+            Client::default(),
+            listener,
+            network.clone(),
+        )
+    }
+
+    // Makes each of `steppers` a player of `group` for all the others.
+    fn connect(steppers: &mut [Stepper], group: &GroupId) {
+        let public_ids: Vec<PublicKey> = steppers
+            .iter()
+            .map(|stepper| stepper.secret_id.public)
+            .collect();
+        for (index, stepper) in steppers.iter_mut().enumerate() {
+            for (other, public_id) in public_ids.iter().enumerate() {
+                if other != index {
+                    let channels = stepper.player_channels.clone();
+                    unwrap!(stepper.add_player(group, *public_id, channels));
+                }
+            }
+        }
+    }
+
+    // Polls each of `steppers` in turn, `rounds` times.
+    fn run(steppers: &mut [Stepper], rounds: usize) {
+        for _ in 0..rounds {
+            for stepper in steppers.iter_mut() {
+                let _ = unwrap!(stepper.poll());
+            }
+        }
+    }
+
+    fn received(stepper: &Stepper) -> BTreeSet<(GroupId, Vec<u8>)> {
+        stepper
+            .client
+            .rumors
+            .iter()
+            .map(|(group, content)| (group.clone(), content.value.to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn rumors_stay_within_their_group() {
+        let network = Channels::default();
+        let config = Config::default();
+        let mut steppers = vec![stepper(&network, &config), stepper(&network, &config)];
+        let default = GroupId::default();
+        let other = GroupId("other".to_string());
+        for stepper in &mut steppers {
+            let id = stepper.our_id();
+            let _ = stepper.add_group(other.clone(), Gossiping::new(id, BTreeSet::new()));
+        }
+        connect(&mut steppers, &default);
+        connect(&mut steppers, &other);
+
+        let commands = &mut steppers[0].client.commands;
+        commands.push_back(ClientCmd::NewRumor(default.clone(), Content::new(vec![1])));
+        commands.push_back(ClientCmd::NewRumor(other.clone(), Content::new(vec![2])));
+        run(&mut steppers, 10);
+
+        let expected = vec![(default.clone(), vec![1]), (other.clone(), vec![2])];
+        assert_eq!(received(&steppers[1]), expected.into_iter().collect());
+        assert!(steppers[1].groups[&default].rumors().contains_key(&[1][..]));
+        assert!(!steppers[1].groups[&default].rumors().contains_key(&[2][..]));
+        assert!(steppers[1].groups[&other].rumors().contains_key(&[2][..]));
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor_groups_negotiate_and_gossip() {
+        use crate::codec::Codec;
+
+        let network = Channels::default();
+        let config = Config {
+            codec: Codec::Cbor,
+            ..Config::default()
+        };
+        let mut steppers = vec![stepper(&network, &config), stepper(&network, &config)];
+        connect(&mut steppers, &GroupId::default());
+        let commands = &mut steppers[0].client.commands;
+        commands.push_back(ClientCmd::NewRumor(
+            GroupId::default(),
+            Content::new(vec![1]),
+        ));
+        run(&mut steppers, 10);
+
+        for (stepper, other) in [(0, 1), (1, 0)] {
+            let other = steppers[other].our_id();
+            assert_eq!(
+                steppers[stepper].versions.get(&other),
+                Some(&PROTOCOL_VERSION)
+            );
+        }
+        let expected = vec![(GroupId::default(), vec![1])];
+        assert_eq!(received(&steppers[1]), expected.into_iter().collect());
+    }
+
+    #[test]
+    fn encrypted_groups_gossip() {
+        let network = Channels::default();
+        let config = Config {
+            encryption: Encryption::XChaCha20Poly1305,
+            ..Config::default()
+        };
+        let mut steppers = vec![stepper(&network, &config), stepper(&network, &config)];
+        connect(&mut steppers, &GroupId::default());
+        let commands = &mut steppers[0].client.commands;
+        commands.push_back(ClientCmd::NewRumor(
+            GroupId::default(),
+            Content::new(vec![1]),
+        ));
+        let _ = unwrap!(steppers[0].poll());
+
+        // Every frame sent, the push among them, has its payload encrypted.
+        let (sender, id) = (steppers[0].our_id(), steppers[1].our_id());
+        let frames = unwrap!(network.0.borrow().inboxes.get(&id).cloned());
+        assert!(frames
+            .iter()
+            .all(|(_, frame)| frame[9] == Encryption::XChaCha20Poly1305.id()));
+        run(&mut steppers, 10);
+
+        assert!(steppers[1].sessions.contains_key(&sender));
+        let expected = vec![(GroupId::default(), vec![1])];
+        assert_eq!(received(&steppers[1]), expected.into_iter().collect());
+    }
+
+    #[test]
+    fn clear_frames_are_rejected_by_encrypted_groups() {
+        let network = Channels::default();
+        let encrypted = Config {
+            encryption: Encryption::XChaCha20Poly1305,
+            ..Config::default()
+        };
+        let mut steppers = vec![
+            stepper(&network, &Config::default()),
+            stepper(&network, &encrypted),
+        ];
+        connect(&mut steppers, &GroupId::default());
+        let _ = unwrap!(steppers[0].poll());
+        match steppers[1].receive_from_players() {
+            Err(Error::NotEncrypted) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        let commands = &mut steppers[0].client.commands;
+        commands.push_back(ClientCmd::NewRumor(
+            GroupId::default(),
+            Content::new(vec![1]),
+        ));
+        run(&mut steppers, 10);
+        assert!(steppers[1].client.rumors.is_empty());
+        // The sender may just not have been told to encrypt.
+        let sender = steppers[0].our_id();
+        assert_eq!(
+            steppers[1].score(&sender),
+            ReputationConfig::default().max_score
+        );
+    }
+
+    #[test]
+    fn players_given_at_construction_are_greeted() {
+        let network = Channels::default();
+        let keys: Vec<Keypair> = (0..2)
+            .map(|_| Keypair::generate::<sha3::Sha3_512, _>(&mut rand::thread_rng()))
+            .collect();
+        let ids: BTreeSet<Id> = keys.iter().map(|keys| keys.public.id()).collect();
+        let mut steppers: Vec<Stepper> = keys
+            .into_iter()
+            .map(|keys| {
+                let id = keys.public.id();
+                let players = ids
+                    .iter()
+                    .filter(|&&other| other != id)
+                    .map(|&id| Player { id })
+                    .collect();
+                let listener = Listener {
+                    id,
+                    network: network.clone(),
+                };
+                GossipStepper::new(
+                    keys,
+                    Gossiping::new(id, players),
+                    Client::default(),
+                    listener,
+                    network.clone(),
+                )
+            })
+            .collect();
+        let commands = &mut steppers[0].client.commands;
+        commands.push_back(ClientCmd::NewRumor(
+            GroupId::default(),
+            Content::new(vec![1]),
+        ));
+        run(&mut steppers, 10);
+
+        for (stepper, other) in [(0, 1), (1, 0)] {
+            let other = steppers[other].our_id();
+            assert_eq!(
+                steppers[stepper].versions.get(&other),
+                Some(&PROTOCOL_VERSION)
+            );
+        }
+        let expected = vec![(GroupId::default(), vec![1])];
+        assert_eq!(received(&steppers[1]), expected.into_iter().collect());
+    }
+
+    #[test]
+    fn only_requested_bootstraps_are_accepted() {
+        let network = Channels::default();
+        let config = Config::default();
+        let mut steppers: Vec<_> = (0..3).map(|_| stepper(&network, &config)).collect();
+        let default = GroupId::default();
+        connect(&mut steppers[..2], &default);
+        let commands = &mut steppers[0].client.commands;
+        commands.push_back(ClientCmd::NewRumor(default.clone(), Content::new(vec![1])));
+        run(&mut steppers, 4);
+        let (public_id, joiner) = (steppers[0].secret_id.public, steppers[2].secret_id.public);
+        let channels = steppers[0].player_channels.clone();
+        unwrap!(steppers[0].add_player(&default, joiner, channels));
+
+        // A bootstrap nobody asked for is dropped.
+        let joiner = joiner.id();
+        let bootstrap = unwrap!(steppers[0].groups[&default].receive_bootstrap_request(joiner));
+        unwrap!(steppers[0].send(&default, joiner, &Message::Bootstrap(bootstrap)));
+        match steppers[2].receive_from_players() {
+            Err(Error::UnsolicitedBootstrap) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert!(steppers[2].client.rumors.is_empty());
+
+        unwrap!(steppers[2].request_bootstrap(&default, &[public_id]));
+        run(&mut steppers, 2);
+        let expected = vec![(default, vec![1])];
+        assert_eq!(received(&steppers[2]), expected.into_iter().collect());
+    }
+
+    #[test]
+    fn bootstraps_are_only_sent_to_players() {
+        let network = Channels::default();
+        let config = Config::default();
+        let mut steppers: Vec<_> = (0..3).map(|_| stepper(&network, &config)).collect();
+        let default = GroupId::default();
+        connect(&mut steppers[..2], &default);
+        let commands = &mut steppers[0].client.commands;
+        commands.push_back(ClientCmd::NewRumor(default.clone(), Content::new(vec![1])));
+        run(&mut steppers, 4);
+
+        // The last stepper is in no group, yet signs its request with its own key.
+        let public_id = steppers[0].secret_id.public;
+        unwrap!(steppers[2].request_bootstrap(&default, &[public_id]));
+        run(&mut steppers, 4);
+        assert!(steppers[2].client.rumors.is_empty());
+    }
+
+    #[test]
+    fn replayed_frames_are_rejected() {
+        let network = Channels::default();
+        let config = Config::default();
+        let mut steppers = vec![stepper(&network, &config), stepper(&network, &config)];
+        let id = steppers[1].our_id();
+        let inbox = |network: &Channels| unwrap!(network.0.borrow().inboxes.get(&id).cloned());
+        let replay = |stepper: &mut Stepper, frames: VecDeque<Frame>| {
+            let _ = network.0.borrow_mut().inboxes.insert(id, frames);
+            match stepper.receive_from_players() {
+                Err(Error::Replay) => (),
+                result => panic!("Unexpected result {:?}", result),
+            }
+        };
+        connect(&mut steppers, &GroupId::default());
+
+        // The `Hello` was sent before any version was agreed on.
+        let hello = inbox(&network);
+        unwrap!(steppers[1].receive_from_players());
+        replay(&mut steppers[1], hello.clone());
+
+        run(&mut steppers, 2);
+        let commands = &mut steppers[0].client.commands;
+        commands.push_back(ClientCmd::NewRumor(
+            GroupId::default(),
+            Content::new(vec![1]),
+        ));
+        let _ = unwrap!(steppers[0].poll());
+        let push = inbox(&network);
+        unwrap!(steppers[1].receive_from_players());
+        replay(&mut steppers[1], push);
+        replay(&mut steppers[1], hello.clone());
+
+        // Every frame has a sequence number, so even one captured long before is rejected.
+        let subscriptions =
+            Message::Subscriptions(steppers[0].groups[&GroupId::default()].subscriptions());
+        for _ in 0..100 {
+            unwrap!(steppers[0].send(&GroupId::default(), id, &subscriptions));
+            unwrap!(steppers[1].receive_from_players());
+        }
+        replay(&mut steppers[1], hello);
+    }
+
+    #[test]
+    fn reordering_is_told_apart_from_replays_per_recipient() {
+        let network = Channels::default();
+        let config = Config::default();
+        let mut steppers: Vec<_> = (0..3).map(|_| stepper(&network, &config)).collect();
+        let default = GroupId::default();
+        connect(&mut steppers, &default);
+        run(&mut steppers, 2);
+
+        // Many transmissions to another player between two to the same one don't make the
+        // first look too old once they arrive out of order.
+        let (id, other) = (steppers[1].our_id(), steppers[2].our_id());
+        let hello = Message::Hello(Versions::ours());
+        unwrap!(steppers[0].send(&default, id, &hello));
+        let first = unwrap!(network.0.borrow_mut().inboxes.remove(&id));
+        for _ in 0..100 {
+            unwrap!(steppers[0].send(&default, other, &hello));
+        }
+        unwrap!(steppers[0].send(&default, id, &hello));
+        network
+            .0
+            .borrow_mut()
+            .inboxes
+            .entry(id)
+            .or_default()
+            .extend(first);
+        unwrap!(steppers[1].receive_from_players());
+    }
+
+    #[test]
+    fn key_rotations() {
+        let network = Channels::default();
+        let config = Config::default();
+        let mut steppers = vec![stepper(&network, &config), stepper(&network, &config)];
+        let default = GroupId::default();
+        connect(&mut steppers, &default);
+        run(&mut steppers, 2);
+
+        let generate = || Keypair::generate::<sha3::Sha3_512, _>(&mut rand::thread_rng());
+        let rotation = |old: Id, new: &Keypair, signer: &Keypair| {
+            let proof = unwrap!(KeyRotation::proof(&old, &new.public.id()));
+            KeyRotation {
+                public_id: PublicId::to_bytes(&new.public),
+                sig: SecretId::sign(signer, &proof),
+            }
+        };
+        let old = steppers[0].our_id();
+
+        // The new key must sign the rotation, so that no player can claim another's key.
+        let (new, other) = (generate(), generate());
+        match steppers[1].receive_key_rotation(old, &rotation(old, &new, &other)) {
+            Err(Error::SigFailure) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        // Only players of our groups can rotate their key.
+        let stranger = other.public.id();
+        match steppers[1].receive_key_rotation(stranger, &rotation(stranger, &new, &new)) {
+            Err(Error::InvalidKeyRotation) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert!(!steppers[1].public_ids.contains_key(&new.public.id()));
+
+        let new_id = new.public.id();
+        unwrap!(steppers[0].rotate_key(new));
+        run(&mut steppers, 2);
+        let players = steppers[1].groups[&default].players();
+        assert!(players.contains(&Player { id: new_id }));
+        assert!(!players.contains(&Player { id: old }));
+    }
+
+    #[test]
+    fn failed_key_rotations_change_no_group() {
+        let network = Channels::default();
+        let config = Config::default();
+        let mut steppers = vec![stepper(&network, &config), stepper(&network, &config)];
+        let default = GroupId::default();
+        connect(&mut steppers, &default);
+        run(&mut steppers, 2);
+
+        // The new key is already that of a player in the other group, but not in the default
+        // one, which comes first.
+        let keys = Keypair::generate::<sha3::Sha3_512, _>(&mut rand::thread_rng());
+        let (old, new) = (steppers[0].our_id(), keys.public.id());
+        let other = GroupId("other".to_string());
+        let mut gossiping = Gossiping::new(steppers[1].our_id(), BTreeSet::new());
+        unwrap!(gossiping.add_player(old));
+        unwrap!(gossiping.add_player(new));
+        let _ = steppers[1].add_group(other.clone(), gossiping);
+
+        let proof = unwrap!(KeyRotation::proof(&old, &new));
+        let rotation = KeyRotation {
+            public_id: PublicId::to_bytes(&keys.public),
+            sig: SecretId::sign(&keys, &proof),
+        };
+        match steppers[1].receive_key_rotation(old, &rotation) {
+            Err(Error::InvalidKeyRotation) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        for group in &[default, other] {
+            assert!(steppers[1].groups[group]
+                .players()
+                .contains(&Player { id: old }));
+        }
+        assert!(!steppers[1].groups[&GroupId::default()]
+            .players()
+            .contains(&Player { id: new }));
+        assert!(steppers[1].public_ids.contains_key(&old));
+    }
+
+    #[test]
+    fn failed_replies_are_not_held_against_the_sender() {
+        let network = Channels::default();
+        let config = Config::default();
+        let mut steppers = vec![stepper(&network, &config), stepper(&network, &config)];
+        connect(&mut steppers, &GroupId::default());
+        run(&mut steppers, 2);
+
+        let commands = &mut steppers[0].client.commands;
+        commands.push_back(ClientCmd::NewRumor(
+            GroupId::default(),
+            Content::new(vec![1]),
+        ));
+        let _ = unwrap!(steppers[0].poll());
+        let sender = steppers[0].our_id();
+        let _ = network.0.borrow_mut().broken.insert(sender);
+        match steppers[1].receive_from_players() {
+            Err(Error::Encoding(_)) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert_eq!(received(&steppers[1]).len(), 1);
+        assert_eq!(
+            steppers[1].score(&sender),
+            ReputationConfig::default().max_score
+        );
+    }
+
+    #[test]
+    fn forged_callers_are_rejected() {
+        let network = Channels::default();
+        let config = Config::default();
+        let mut steppers = vec![stepper(&network, &config), stepper(&network, &config)];
+        let default = GroupId::default();
+        connect(&mut steppers, &default);
+        run(&mut steppers, 2);
+
+        // The gossip names another player as its caller than the one signing it.
+        let keys = Keypair::generate::<sha3::Sha3_512, _>(&mut rand::thread_rng());
+        let mut gossiping = Gossiping::new(keys.public.id(), BTreeSet::new());
+        unwrap!(gossiping.add_player(steppers[1].our_id()));
+        unwrap!(gossiping.initiate_rumor(Content::new(vec![1])));
+        let push = unwrap!(gossiping.collect_gossip());
+        unwrap!(steppers[0].send(&default, push.callee.id, &Message::Push(push)));
+
+        match steppers[1].receive_from_players() {
+            Err(Error::CallerMismatch) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        let sender = steppers[0].our_id();
+        assert!(steppers[1].client.rumors.is_empty());
+        let config = ReputationConfig::default();
+        assert_eq!(
+            steppers[1].score(&sender),
+            config.max_score - config.caller_mismatch_penalty
+        );
+
+        // Nor can subscriptions be sent on behalf of another player.
+        let subscriptions = Message::Subscriptions(Subscriptions {
+            player: keys.public.id(),
+            topics: BTreeSet::new(),
+        });
+        let id = steppers[1].our_id();
+        unwrap!(steppers[0].send(&default, id, &subscriptions));
+        match steppers[1].receive_from_players() {
+            Err(Error::CallerMismatch) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn implausible_states_are_penalised() {
+        let network = Channels::default();
+        let config = Config::default();
+        let mut steppers = vec![stepper(&network, &config), stepper(&network, &config)];
+        let default = GroupId::default();
+        connect(&mut steppers, &default);
+        run(&mut steppers, 2);
+
+        // Nobody can have taken a rumor we just initiated to state C.
+        let content = Content::new(vec![1]);
+        let gossiping = unwrap!(steppers[1].group_mut(&default));
+        unwrap!(gossiping.initiate_rumor(content.clone()));
+        let (sender, id) = (steppers[0].our_id(), steppers[1].our_id());
+        let push = Gossip {
+            callee: ObliviousPlayer { id },
+            rumors: vec![Rumor {
+                content,
+                callee: ObliviousPlayer { id },
+                state: State::C {
+                    rounds_in_state_b: Round::default(),
+                    round: Round::default(),
+                },
+                caller: InformedPlayer { id: sender },
+            }],
+            caller: InformedPlayer { id: sender },
+        };
+        unwrap!(steppers[0].send(&default, id, &Message::Push(push)));
+        match steppers[1].receive_from_players() {
+            Err(Error::ImplausibleState) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        let config = ReputationConfig::default();
+        assert_eq!(
+            steppers[1].score(&sender),
+            config.max_score - config.implausible_state_penalty
+        );
+    }
+
+    #[test]
+    fn misbehaving_players_are_quarantined_and_released() {
+        let network = Channels::default();
+        let config = Config::default();
+        let reputation = ReputationConfig {
+            recovery_interval: Duration::from_secs(3600),
+            quarantine_duration: Duration::from_millis(0),
+            ..ReputationConfig::default()
+        };
+        let mut steppers = vec![
+            stepper(&network, &config),
+            stepper(&network, &config).with_reputation(reputation.clone()),
+        ];
+        let default = GroupId::default();
+        connect(&mut steppers, &default);
+        run(&mut steppers, 2);
+        let (sender, id) = (steppers[0].our_id(), steppers[1].our_id());
+
+        // Frames whose signature can't be checked may come from anyone, whoever the transport
+        // says sent them.
+        let victim = steppers[0].secret_id.public;
+        let garbage = (0..10).map(|_| (victim, b"garbage".to_vec())).collect();
+        let _ = network.0.borrow_mut().inboxes.insert(id, garbage);
+        match steppers[1].receive_from_players() {
+            Err(Error::InvalidFrame) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert_eq!(steppers[1].score(&sender), reputation.max_score);
+        assert!(steppers[1].client.quarantines.is_empty());
+
+        // Transmissions signed by the sender are held against it.
+        let subscriptions = Message::Subscriptions(Subscriptions {
+            player: Id([1; 32]),
+            topics: BTreeSet::new(),
+        });
+        let count = reputation.max_score / reputation.caller_mismatch_penalty;
+        for _ in 0..count {
+            unwrap!(steppers[0].send(&default, id, &subscriptions));
+        }
+        match steppers[1].receive_from_players() {
+            Err(Error::CallerMismatch) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert_eq!(steppers[1].score(&sender), 0);
+        assert_eq!(
+            steppers[1].client.quarantines,
+            vec![QuarantineEvent::Quarantined(sender)]
+        );
+        assert!(steppers[1].reputation.is_quarantined(&sender));
+
+        // The quarantine is over on the next poll.
+        let _ = unwrap!(steppers[1].poll());
+        assert_eq!(
+            steppers[1].client.quarantines,
+            vec![
+                QuarantineEvent::Quarantined(sender),
+                QuarantineEvent::Released(sender)
+            ]
+        );
+        assert!(!steppers[1].reputation.is_quarantined(&sender));
+        assert_eq!(steppers[1].score(&sender), reputation.max_score);
+    }
+
+    #[test]
+    fn blocked_transmissions_are_deferred() {
+        let network = Channels::default();
+        let config = Config::default();
+        let mut steppers = vec![stepper(&network, &config), stepper(&network, &config)];
+        let default = GroupId::default();
+        connect(&mut steppers, &default);
+        run(&mut steppers, 2);
+
+        let id = steppers[1].our_id();
+        let _ = network.0.borrow_mut().blocked.insert(id);
+        let commands = &mut steppers[0].client.commands;
+        commands.push_back(ClientCmd::NewRumor(default.clone(), Content::new(vec![1])));
+        let _ = unwrap!(steppers[0].poll());
+        assert!(!network.0.borrow().inboxes.contains_key(&id));
+        assert!(!steppers[0].deferred[&id].is_empty());
+
+        // Only so many are held back.
+        let hello = Message::Hello(Versions::ours());
+        while steppers[0].deferred[&id].len() < MAX_DEFERRED {
+            unwrap!(steppers[0].send(&default, id, &hello));
+        }
+        match steppers[0].send(&default, id, &hello) {
+            Err(Error::WouldBlock) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert_eq!(steppers[0].deferred[&id].len(), MAX_DEFERRED);
+
+        // Once the transport takes them again, they're all sent.
+        let _ = network.0.borrow_mut().blocked.remove(&id);
+        run(&mut steppers, 4);
+        assert!(steppers[0].deferred.is_empty());
+        let expected = vec![(default, vec![1])];
+        assert_eq!(received(&steppers[1]), expected.into_iter().collect());
+    }
+
+    #[test]
+    fn flooding_senders_are_dropped() {
+        let network = Channels::default();
+        let config = Config::default();
+        let limits = RateLimitConfig {
+            messages_per_sec: Some(2),
+            ..RateLimitConfig::default()
+        };
+        let mut steppers = vec![
+            stepper(&network, &config),
+            stepper(&network, &config).with_rate_limit(limits),
+        ];
+        let default = GroupId::default();
+        connect(&mut steppers, &default);
+
+        // The `Hello` and subscriptions sent on connecting use up the bucket.
+        let id = steppers[1].our_id();
+        let subscriptions = Message::Subscriptions(steppers[0].groups[&default].subscriptions());
+        for _ in 0..10 {
+            unwrap!(steppers[0].send(&default, id, &subscriptions));
+        }
+        unwrap!(steppers[1].receive_from_players());
+        assert_eq!(steppers[1].rate_limited(), 10);
+        let sender = steppers[0].our_id();
+        assert_eq!(steppers[1].versions.get(&sender), Some(&PROTOCOL_VERSION));
+    }
+
+    #[test]
+    fn flooding_senders_are_delayed() {
+        let network = Channels::default();
+        let config = Config::default();
+        let limits = RateLimitConfig {
+            messages_per_sec: Some(1000),
+            burst: Duration::from_millis(2),
+            overflow: Overflow::Delay,
+            ..RateLimitConfig::default()
+        };
+        let mut steppers = vec![
+            stepper(&network, &config),
+            stepper(&network, &config).with_rate_limit(limits),
+        ];
+        let default = GroupId::default();
+        connect(&mut steppers, &default);
+
+        // The push comes behind more transmissions than the bucket holds.
+        let id = steppers[1].our_id();
+        let subscriptions = Message::Subscriptions(steppers[0].groups[&default].subscriptions());
+        for _ in 0..10 {
+            unwrap!(steppers[0].send(&default, id, &subscriptions));
+        }
+        let commands = &mut steppers[0].client.commands;
+        commands.push_back(ClientCmd::NewRumor(default.clone(), Content::new(vec![1])));
+        run(&mut steppers[..1], 2);
+        unwrap!(steppers[1].receive_from_players());
+        assert!(steppers[1].client.rumors.is_empty());
+
+        // It's handled on a later poll, once the bucket refilled enough.
+        for _ in 0..100 {
+            if !steppers[1].client.rumors.is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+            let _ = unwrap!(steppers[1].poll());
+        }
+        let expected = vec![(default, vec![1])];
+        assert_eq!(received(&steppers[1]), expected.into_iter().collect());
+        assert_eq!(steppers[1].rate_limited(), 0);
+    }
+
+    #[test]
+    fn frames_to_unknown_groups_are_rejected() {
+        let network = Channels::default();
+        let config = Config::default();
+        let mut steppers = vec![stepper(&network, &config), stepper(&network, &config)];
+        let default = GroupId::default();
+        connect(&mut steppers, &default);
+        run(&mut steppers, 2);
+
+        // Only the sender knows of the group.
+        let unknown = GroupId("unknown".to_string());
+        let id = steppers[0].our_id();
+        let mut gossiping = Gossiping::new(id, BTreeSet::new());
+        unwrap!(gossiping.add_player(steppers[1].our_id()));
+        unwrap!(gossiping.initiate_rumor(Content::new(vec![1])));
+        let push = unwrap!(gossiping.collect_gossip());
+        let _ = steppers[0].add_group(unknown.clone(), Gossiping::new(id, BTreeSet::new()));
+        unwrap!(steppers[0].send(&unknown, push.callee.id, &Message::Push(push)));
+
+        match steppers[1].receive_from_players() {
+            Err(Error::UnknownGroup) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        assert!(steppers[1].client.rumors.is_empty());
+        assert_eq!(
+            steppers[1].score(&id),
+            ReputationConfig::default().max_score
+        );
+    }
+}
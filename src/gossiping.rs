@@ -7,31 +7,72 @@
 // specific language governing permissions and limitations relating to use of the SAFE Network
 // Software.
 
+use crate::config::Config;
 use crate::error::Error;
-use crate::gossip::{Content, Gossip, InformedPlayer, ObliviousPlayer, Player, Rumor};
+use crate::gossip::{
+    Content, Gossip, InformedPlayer, ObliviousPlayer, Player, Rumor, Subscriptions, Topic,
+};
 use crate::id::Id;
+use crate::player_set::{PlayerIndices, PlayerSet};
 use crate::state::{Age, Round, State};
+use bytes::Bytes;
 use rand::seq::SliceRandom;
 use std::cmp;
+use std::collections::btree_map::Entry;
 use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
+use std::time::Instant;
 
-type ContentHash = Vec<u8>;
+type ContentHash = Bytes;
 
 /// An instance of Gossiping holds the state
 /// necessary to carry out gossiping in a cluster.
+#[derive(Clone)]
 pub struct Gossiping {
     our_id: Id,
     rumors: BTreeMap<ContentHash, RumorProgress>,
     players: BTreeSet<Player>,
+    // The indices of the players in the sets of each rumor, which outlive the players.
+    indices: PlayerIndices,
+    // The indices of the current players.
+    members: PlayerSet,
+    // The topics each player has advertised it is subscribed to.
+    subscriptions: BTreeMap<Id, BTreeSet<Topic>>,
+    our_topics: BTreeSet<Topic>,
+    // The players left out of the push rounds for misbehaving.
+    quarantined: BTreeSet<Id>,
+    // The players we asked for the rumors they recently saw, and which haven't answered yet.
+    bootstrap_requests: BTreeSet<Id>,
+    config: Config,
+    // Rumors we have learned about from other players, but not yet handed to the client.
+    new_rumors: Vec<Content>,
 }
 
 impl Gossiping {
     /// Returns a new instance of the Gossiping, to be used by a player in a cluster.
     pub fn new(our_id: Id, players: BTreeSet<Player>) -> Gossiping {
+        Self::with_config(our_id, players, Config::default())
+    }
+
+    /// Returns a new instance of the Gossiping using the given `config`.
+    pub fn with_config(our_id: Id, players: BTreeSet<Player>, config: Config) -> Gossiping {
+        let mut indices = PlayerIndices::default();
+        let mut members = PlayerSet::default();
+        for player in &players {
+            let _ = members.insert(indices.insert(player.id));
+        }
         Gossiping {
             our_id,
             rumors: BTreeMap::new(),
             players,
+            indices,
+            members,
+            subscriptions: BTreeMap::new(),
+            our_topics: BTreeSet::new(),
+            quarantined: BTreeSet::new(),
+            bootstrap_requests: BTreeSet::new(),
+            config,
+            new_rumors: vec![],
         }
     }
 
@@ -45,11 +86,80 @@ impl Gossiping {
         &self.rumors
     }
 
+    /// Returns our configuration.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Returns all players in the cluster.
+    pub fn players(&self) -> &BTreeSet<Player> {
+        &self.players
+    }
+
+    /// Leaves a player out of the push rounds until it's released.  It still receives the
+    /// responses to its own pushes.
+    pub fn quarantine(&mut self, player_id: Id) {
+        let _ = self.quarantined.insert(player_id);
+    }
+
+    /// Lets a quarantined player back into the push rounds.
+    pub fn release(&mut self, player_id: Id) {
+        let _ = self.quarantined.remove(&player_id);
+    }
+
+    /// Subscribes us to the rumors of `topic`.
+    /// Other players learn about it through the advertisement returned by `subscriptions()`.
+    pub fn subscribe(&mut self, topic: Topic) {
+        let _ = self.our_topics.insert(topic);
+    }
+
+    /// Unsubscribes us from the rumors of `topic`.
+    pub fn unsubscribe(&mut self, topic: &Topic) {
+        let _ = self.our_topics.remove(topic);
+    }
+
+    /// Returns the advertisement of the topics we are subscribed to.
+    pub fn subscriptions(&self) -> Subscriptions {
+        Subscriptions {
+            player: self.our_id,
+            topics: self.our_topics.clone(),
+        }
+    }
+
+    /// Receives the advertisement of the topics a player is subscribed to, replacing any
+    /// previous advertisement from that player.  Advertisements from anyone but the players of
+    /// the group are ignored, so that nobody else can subscribe to its rumors.
+    pub fn receive_subscriptions(&mut self, subscriptions: Subscriptions) {
+        if !self.players.contains(&Player {
+            id: subscriptions.player,
+        }) {
+            return;
+        }
+        let _ = self
+            .subscriptions
+            .insert(subscriptions.player, subscriptions.topics);
+    }
+
+    /// Returns the rumors learned from other players since the last call, in the order they
+    /// were first received.
+    /// Rumors which have passed their deadline in the meantime are not returned.
+    pub fn take_new_rumors(&mut self) -> Vec<Content> {
+        let tolerance = self.config.clock_skew_tolerance;
+        mem::take(&mut self.new_rumors)
+            .into_iter()
+            .filter(|content| !content.is_expired(tolerance))
+            .collect()
+    }
+
     /// Adds a player. This does not affect any ongoing Rumors.
     pub fn add_player(&mut self, player_id: Id) -> Result<(), Error> {
         // Inserting to set, so no need to check player is not already here.
         // todo: do not discard result
         let _ = self.players.insert(Player { id: player_id });
+        if self.indices.get(&player_id).is_none() {
+            self.free_indices();
+        }
+        let _ = self.members.insert(self.indices.insert(player_id));
 
         // We just play out all rounds and disregard from any membership change whilst in them,
         // therefore, the below is commented out (and shall be removed).
@@ -63,6 +173,17 @@ impl Gossiping {
         Ok(())
     }
 
+    // Frees the indices of the removed players no rumor refers to any more, so that the sets
+    // of the rumors don't keep growing as players come and go.
+    fn free_indices(&mut self) {
+        let mut used = self.members.clone();
+        for ongoing in self.rumors.values() {
+            used.union_with(&ongoing.informed);
+            used.union_with(&ongoing.oblivious);
+        }
+        self.indices.retain(&used);
+    }
+
     /// Removes a player. This does not affect any ongoing Rumors.
     pub fn remove_player(&mut self, player_id: Id) {
         self.players = self
@@ -71,6 +192,11 @@ impl Gossiping {
             .filter(|c| c.id != player_id)
             .copied()
             .collect();
+        if let Some(index) = self.indices.get(&player_id) {
+            let _ = self.members.remove(index);
+        }
+        let _ = self.subscriptions.remove(&player_id);
+        let _ = self.quarantined.remove(&player_id);
 
         // We just play out all rounds and disregard from any membership change whilst in them,
         // therefore, the below is commented out (and shall be removed).
@@ -89,11 +215,45 @@ impl Gossiping {
         // // }
     }
 
+    /// Replaces the id of a player which rotated its key, `old`, by its new one, keeping its
+    /// subscriptions and its place in the ongoing rumors.  This may be our own id.
+    pub fn rotate_player(&mut self, old: Id, new: Id) -> Result<(), Error> {
+        if !self.can_rotate_player(old, new) {
+            return Err(Error::InvalidKeyRotation);
+        }
+        if self.our_id == old {
+            self.our_id = new;
+        } else {
+            let _ = self.players.remove(&Player { id: old });
+            let _ = self.players.insert(Player { id: new });
+            if let Some(topics) = self.subscriptions.remove(&old) {
+                let _ = self.subscriptions.insert(new, topics);
+            }
+            if self.quarantined.remove(&old) {
+                let _ = self.quarantined.insert(new);
+            }
+            if self.bootstrap_requests.remove(&old) {
+                let _ = self.bootstrap_requests.insert(new);
+            }
+            // The rumors hold the player by its index, which it keeps under its new id.
+            self.indices.rotate(&old, new);
+        }
+        Ok(())
+    }
+
+    /// Whether `rotate_player` can replace `old` by `new`: `old` must be us or one of the players,
+    /// and `new` neither.
+    pub fn can_rotate_player(&self, old: Id, new: Id) -> bool {
+        let is_known = |id| id == self.our_id || self.players.contains(&Player { id });
+        is_known(old) && !is_known(new)
+    }
+
     /// Initiates a rumor, which means sending it to some player.
     /// If no players, we will just hold on to the rumor until we know of any players.
     pub fn initiate_rumor(&mut self, content: Content) -> Result<(), Error> {
-        let id = self.hash(content.clone());
-        let cluster_size = self.players.len() as f64;
+        let id = self.hash(&content);
+        let oblivious = self.audience(content.topic.as_ref());
+        let (max_b_age, max_rounds) = round_limits(oblivious.len());
 
         if self
             .rumors
@@ -101,16 +261,14 @@ impl Gossiping {
                 id,
                 RumorProgress {
                     content,
-                    informed_players: vec![],
-                    oblivious_players: self
-                        .players
-                        .iter()
-                        .map(|c| ObliviousPlayer { id: c.id })
-                        .collect(),
+                    informed: PlayerSet::default(),
+                    oblivious,
                     state: State::new(),
-                    max_b_age: Age::from(cmp::max(1, cluster_size.ln().ceil() as u8)),
-                    max_rounds: Round::from(cmp::max(1, cluster_size.ln().ln().ceil() as u8)),
-                    max_c_rounds: Round::from(cmp::max(1, cluster_size.ln().ln().ceil() as u8)),
+                    received: Instant::now(),
+                    initiated: true,
+                    max_b_age,
+                    max_rounds,
+                    max_c_rounds: max_rounds,
                 },
             )
             .is_some()
@@ -126,44 +284,52 @@ impl Gossiping {
 
     /// Incoming rumors is a trigger of sending all rumors that this player has.
     pub fn receive_gossip(&mut self, gossip: &Gossip, is_push: bool) -> Option<Gossip> {
-        let oblivious_players: Vec<ObliviousPlayer> = self
-            .players
-            .iter()
-            .filter(|c| c.id != gossip.caller.id)
-            .map(|c| ObliviousPlayer { id: c.id })
-            .collect();
-
-        let cluster_size = self.players.len() as f64;
-        let max_b_age = Age::from(cmp::max(1, cluster_size.ln().ceil() as u8));
-        let max_rounds = Round::from(cmp::max(1, cluster_size.ln().ln().ceil() as u8));
+        self.drop_expired_rumors();
 
         // if we already have this rumor, update with the incoming rumor age/state
-        for rumor in gossip.rumors.to_vec() {
-            let id = self.hash(rumor.content.clone());
-            // todo: do not discard result.
-            let _ = self
-                .rumors
-                .entry(id)
-                .and_modify(|e| {
-                    e.state.receive_rumor(
-                        rumor.caller.id,
-                        rumor.state.get_age().unwrap_or_else(|| Age::max()),
-                    )
-                })
-                .or_insert(RumorProgress {
+        for rumor in &gossip.rumors {
+            if rumor.content.is_expired(self.config.clock_skew_tolerance)
+                || !self.is_subscribed_to(rumor.content.topic.as_ref())
+            {
+                continue;
+            }
+            let id = self.hash(&rumor.content);
+            let age = rumor.state.get_age().unwrap_or_else(Age::max);
+            // The rumor is credited to the player the gossip came from, whatever caller the
+            // rumor itself names, so that no player can report ages on behalf of others.
+            let caller = gossip.caller.id;
+
+            if let Some(ongoing) = self.rumors.get_mut(&id) {
+                ongoing.state.receive_rumor(caller, age);
+                continue;
+            }
+
+            // The parameters are based on the number of players subscribed to the rumor's topic.
+            let audience = self.audience(rumor.content.topic.as_ref());
+            let (max_b_age, max_rounds) = round_limits(audience.len());
+            // A caller we never knew as a player isn't tracked.
+            let mut informed = PlayerSet::default();
+            let mut oblivious = audience;
+            if let Some(index) = self.indices.get(&caller) {
+                let _ = informed.insert(index);
+                let _ = oblivious.remove(index);
+            }
+
+            self.new_rumors.push(rumor.content.clone());
+            let _ = self.rumors.insert(
+                id,
+                RumorProgress {
                     content: rumor.content.clone(),
-                    informed_players: vec![InformedPlayer {
-                        id: rumor.caller.id,
-                    }], // potential tweak: include their view of this
-                    oblivious_players: oblivious_players.iter().copied().collect(),
-                    state: State::new_from_player(
-                        rumor.state.get_age().unwrap_or_else(|| Age::max()),
-                        max_b_age,
-                    ),
+                    informed, // potential tweak: include their view of this
+                    oblivious,
+                    state: State::new_from_player(caller, age, max_b_age, self.config.age_rule),
+                    received: Instant::now(),
+                    initiated: false,
                     max_b_age,
                     max_rounds,
                     max_c_rounds: max_rounds,
-                });
+                },
+            );
         }
 
         self.try_get_response(gossip, is_push)
@@ -186,10 +352,9 @@ impl Gossiping {
             callee: caller,
             rumors: gossip
                 .rumors
-                .to_vec()
-                .into_iter()
+                .iter()
                 .filter_map(|c| {
-                    let id = self.hash(c.content);
+                    let id = self.hash(&c.content);
                     let ongoing = self.rumors.get(&id)?; // not finding id here would not happen though, since it was added above
                     Some(Rumor {
                         content: ongoing.content.clone(),
@@ -202,33 +367,218 @@ impl Gossiping {
             caller: InformedPlayer { id: our_id },
         };
 
-        // todo: fix reuse of code from collect_gossip(&mut self)
         // We also include any rumors we think it doesn't have.
         // (This will be a distinct set from the ones we received, since we have already registered the receival).
+        // As when pushing, the rumors are moved to their next round only once added.
+        self.add_oblivious_rumors(&mut gossip);
+        self.next_round();
+
+        if !gossip.rumors.is_empty() {
+            return Some(gossip);
+        }
+        None
+    }
+
+    /// Handles a request from a (typically newly joined) player to be brought up to date.
+    /// Returns a `Gossip` holding all rumors we first saw within the configured retention
+    /// window, or `None` if there are no such rumors.  Only players of the group are answered,
+    /// so that nobody else can learn its rumors.
+    pub fn receive_bootstrap_request(&self, player_id: Id) -> Option<Gossip> {
+        if !self.players.contains(&Player { id: player_id }) {
+            return None;
+        }
+        let our_id = self.our_id();
+        let callee = ObliviousPlayer { id: player_id };
+        let rumors: Vec<Rumor> = self
+            .rumors
+            .values()
+            .filter(|ongoing| ongoing.received.elapsed() <= self.config.retention)
+            .filter(|ongoing| !ongoing.content.is_expired(self.config.clock_skew_tolerance))
+            .filter(|ongoing| self.has_subscribed(player_id, ongoing.content.topic.as_ref()))
+            .map(|ongoing| Rumor {
+                content: ongoing.content.clone(),
+                callee,
+                state: ongoing.state.clone(),
+                caller: InformedPlayer { id: our_id },
+            })
+            .collect();
+        if rumors.is_empty() {
+            return None;
+        }
+        Some(Gossip {
+            callee,
+            rumors,
+            caller: InformedPlayer { id: our_id },
+        })
+    }
+
+    /// Records that we ask `player_id` for the rumors it recently saw, so that its answer is
+    /// accepted by `receive_bootstrap()`.
+    pub fn request_bootstrap(&mut self, player_id: Id) {
+        let _ = self.bootstrap_requests.insert(player_id);
+    }
+
+    /// Receives the response to a bootstrap request.
+    /// Rumors we didn't know about are made available via `take_new_rumors()`, but we don't
+    /// take part in propagating them: the rest of the cluster is already doing that.
+    /// Only the first response of a player we asked by `request_bootstrap()` is accepted, since
+    /// its rumors are taken as done with, without any round to tell them apart from made up ones.
+    pub fn receive_bootstrap(&mut self, gossip: &Gossip) -> Result<(), Error> {
+        if !self.bootstrap_requests.remove(&gossip.caller.id) {
+            return Err(Error::UnsolicitedBootstrap);
+        }
+        for rumor in &gossip.rumors {
+            if rumor.content.is_expired(self.config.clock_skew_tolerance)
+                || !self.is_subscribed_to(rumor.content.topic.as_ref())
+            {
+                continue;
+            }
+            let id = self.hash(&rumor.content);
+            if let Entry::Vacant(entry) = self.rumors.entry(id) {
+                let mut informed = PlayerSet::default();
+                if let Some(index) = self.indices.get(&gossip.caller.id) {
+                    let _ = informed.insert(index);
+                }
+                self.new_rumors.push(rumor.content.clone());
+                let _ = entry.insert(RumorProgress {
+                    content: rumor.content.clone(),
+                    informed,
+                    oblivious: PlayerSet::default(),
+                    state: State::D,
+                    received: Instant::now(),
+                    initiated: false,
+                    max_b_age: Age::from(1),
+                    max_rounds: Round::from(1),
+                    max_c_rounds: Round::from(1),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns whether the rumors of `gossip` are all in a state a player following the
+    /// protocol can be in.  Besides the states no player can ever be in, state C is ruled out
+    /// for the rumors we initiated fewer than `max_b_age` rounds ago: nobody had them before
+    /// us, and an age takes that many rounds to get there.
+    pub fn is_plausible(&self, gossip: &Gossip) -> bool {
+        gossip.rumors.iter().all(|rumor| {
+            let too_young = || {
+                self.rumors
+                    .get(&self.hash(&rumor.content))
+                    .is_some_and(RumorProgress::is_too_young_for_state_c)
+            };
+            match rumor.state {
+                State::C { .. } => !too_young(),
+                ref state => state.is_plausible(),
+            }
+        })
+    }
+
+    /// Returns the single Gossip to send to another Player,
+    /// (whom we believe to be an ObliviousPlayer, for all Rumors in this Gossip),
+    /// then moves each Rumor state to next round.  A rumor is pushed in the round it's
+    /// initiated or received in, so that one given a single round still spreads.
+    pub fn collect_gossip(&mut self) -> Option<Gossip> {
+        self.start_round();
+        let gossip = self
+            .choose_callee()
+            .and_then(|player_id| self.gossip_to(player_id));
+        self.next_round();
+        gossip
+    }
+
+    /// Picks at random one of the players believed to be oblivious about any active rumor.
+    fn choose_callee(&self) -> Option<Id> {
+        // The players believed to be oblivious about any Rumor that is not yet completed.
+        let mut oblivious = PlayerSet::default();
+        for ongoing in self.rumors.values().filter(|c| c.state != State::D) {
+            oblivious.union_with(&ongoing.oblivious);
+        }
+
+        oblivious.intersect_with(&self.members);
+
+        let rng = &mut rand::thread_rng(); // put rng as a field of Gossiping instance instead?
+        let players: Vec<Id> = self
+            .indices
+            .ids(&oblivious)
+            .filter(|player_id| !self.quarantined.contains(player_id))
+            .collect();
+
+        // Send to one of them at random.
+        // (We only want to send to one player at a time.)
+        players.choose(rng).copied()
+    }
+
+    /// Drops the expired rumors, before pushing the others.
+    pub(crate) fn start_round(&mut self) {
+        self.drop_expired_rumors();
+    }
+
+    /// Returns the gossip of the active rumors `player_id` is believed not to have, if any,
+    /// regarding it as informed of those from now on.
+    pub(crate) fn gossip_to(&mut self, player_id: Id) -> Option<Gossip> {
+        let mut gossip = Gossip {
+            callee: ObliviousPlayer { id: player_id },
+            rumors: vec![],
+            caller: InformedPlayer { id: self.our_id },
+        };
+        self.add_oblivious_rumors(&mut gossip);
+        if gossip.rumors.is_empty() {
+            None
+        } else {
+            Some(gossip)
+        }
+    }
+
+    /// Moves each active Rumor state to its next round, once pushed.
+    pub(crate) fn next_round(&mut self) {
         // Exclude any rumors which are completed (in state D).
-        let active_rumors = &mut self.rumors.iter_mut().filter(|(_, c)| c.state != State::D);
-        active_rumors.for_each(|(_, mut ongoing)| {
+        let rule = self.config.age_rule;
+        let active_rumors = self.rumors.values_mut().filter(|c| c.state != State::D);
+        for ongoing in active_rumors {
             // Each rumor has its own cycle of rounds.
             ongoing.state = ongoing.state.clone().next_round(
                 ongoing.max_b_age,
                 ongoing.max_c_rounds,
                 ongoing.max_rounds,
+                rule,
             );
+        }
+    }
 
-            if ongoing.state == State::D {
-                return;
-            }
+    /// Adds to `gossip` the active rumors which its callee is believed not to have, highest
+    /// priority first, for as long as they fit within the configured limits.  Rumors which
+    /// don't fit are left for a later round, with the callee still regarded as oblivious.
+    fn add_oblivious_rumors(&mut self, gossip: &mut Gossip) {
+        let our_id = self.our_id();
+        let callee = gossip.callee;
+        let max_rumors = self.config.max_rumors_per_gossip;
+        let max_bytes = self.config.max_bytes_per_gossip;
+        let codec = self.config.codec;
 
-            let exists = ongoing
-                .oblivious_players
-                .iter()
-                .copied()
-                .find(|c| c.id == caller.id);
+        let index = match self.indices.get(&callee.id) {
+            Some(index) => index,
+            None => return,
+        };
 
-            let callee = match exists {
-                Some(c) => c,
-                None => return,
-            };
+        let mut candidates: Vec<&mut RumorProgress> = self
+            .rumors
+            .values_mut()
+            .filter(|c| c.state != State::D)
+            .filter(|c| c.oblivious.contains(index))
+            .collect();
+        // A stable sort, so rumors of equal priority keep their order.
+        candidates.sort_by_key(|ongoing| cmp::Reverse(ongoing.content.priority));
+
+        let mut bytes: u64 = gossip
+            .rumors
+            .iter()
+            .map(|rumor| codec.rumor_size(rumor))
+            .sum();
+        for ongoing in candidates {
+            if max_rumors.is_some_and(|max| gossip.rumors.len() >= max) {
+                break;
+            }
 
             let rumor = Rumor {
                 content: ongoing.content.clone(),
@@ -237,112 +587,150 @@ impl Gossiping {
                 caller: InformedPlayer { id: our_id },
             };
 
+            // Always send at least one rumor, even if it exceeds the byte limit on its own.
+            // Otherwise, keep looking for smaller rumors which may still fit.
+            let size = codec.rumor_size(&rumor);
+            if !gossip.rumors.is_empty() && max_bytes.is_some_and(|max| bytes + size > max) {
+                continue;
+            }
+            bytes += size;
+
             gossip.rumors.push(rumor);
 
             // Move the player from Oblivious to Informed.
-            ongoing.oblivious_players = ongoing
-                .oblivious_players
-                .iter()
-                .filter(|c| c.id != callee.id)
-                .copied()
-                .collect();
-            ongoing
-                .informed_players
-                .push(InformedPlayer { id: callee.id });
-        });
+            let _ = ongoing.oblivious.remove(index);
+            let _ = ongoing.informed.insert(index);
+        }
+    }
 
-        if !gossip.rumors.is_empty() {
-            return Some(gossip);
+    /// Returns whether we are interested in rumors of `topic`.  Rumors without a topic are of
+    /// interest to everyone.
+    fn is_subscribed_to(&self, topic: Option<&Topic>) -> bool {
+        topic.is_none_or(|topic| self.our_topics.contains(topic))
+    }
+
+    /// Returns whether `player_id` has advertised being subscribed to `topic`.
+    fn has_subscribed(&self, player_id: Id, topic: Option<&Topic>) -> bool {
+        topic.is_none_or(|topic| {
+            self.subscriptions
+                .get(&player_id)
+                .is_some_and(|topics| topics.contains(topic))
+        })
+    }
+
+    /// Returns the players a rumor of `topic` is to be spread to.
+    fn audience(&self, topic: Option<&Topic>) -> PlayerSet {
+        let topic = match topic {
+            Some(topic) => topic,
+            None => return self.members.clone(),
+        };
+        let mut audience = PlayerSet::default();
+        for (player_id, topics) in &self.subscriptions {
+            if let Some(index) = self.indices.get(player_id) {
+                if topics.contains(topic) && self.members.contains(index) {
+                    let _ = audience.insert(index);
+                }
+            }
         }
-        None
+        audience
     }
 
-    /// This moves each Rumor state to next round,
-    /// returning the single Gossip to send to another Player,
-    /// (whom we believe to be an ObliviousPlayer, for all Rumors in this Gossip).
-    pub fn collect_gossip(&mut self) -> Option<Gossip> {
-        let our_id = self.our_id();
+    /// Removes all rumors which have passed their deadline, so that they are neither
+    /// propagated any further nor accepted again.
+    fn drop_expired_rumors(&mut self) {
+        let tolerance = self.config.clock_skew_tolerance;
+        self.rumors
+            .retain(|_, ongoing| !ongoing.content.is_expired(tolerance));
+    }
 
-        // Exclude any rumors which are completed (in state D).
-        let active_rumors = &mut self.rumors.iter_mut().filter(|(_, c)| c.state != State::D);
+    fn hash(&self, content: &Content) -> Bytes {
+        content.value.clone() // todo
+    }
 
-        let rng = &mut rand::thread_rng(); // put rng as a field of Gossiping instance instead?
-        let players: Vec<Player> = self.players.iter().copied().collect();
+    /// Whether any rumor is yet to be done with.
+    #[cfg(test)]
+    pub(crate) fn has_active_rumors(&self) -> bool {
+        self.rumors
+            .values()
+            .any(|ongoing| ongoing.state != State::D)
+    }
 
-        // Shuffle players, send to the first of them that
-        // has any rumors we think it hasn't seen, and then break.
-        // (We only want to send to one player at a time.)
-        // This results in always sending to a Player, if at least
-        // one of them is believed to be oblivious about
-        // a Rumor that is not yet completed.
-        for player in players.choose_multiple(rng, players.len()) {
-            let mut gossip = Gossip {
-                callee: ObliviousPlayer { id: player.id },
-                rumors: vec![],
-                caller: InformedPlayer { id: our_id },
-            };
+    /// The players regarded as informed of the rumor `value`, and those as oblivious of it.
+    #[cfg(test)]
+    fn players_of(&self, value: &[u8]) -> (Vec<Id>, Vec<Id>) {
+        let ongoing = &self.rumors[value];
+        (
+            self.indices.ids(&ongoing.informed).collect(),
+            self.indices.ids(&ongoing.oblivious).collect(),
+        )
+    }
 
-            active_rumors.for_each(|(_, mut ongoing)| {
-                // Each rumor has its own cycle of rounds.
-                ongoing.state = ongoing.state.clone().next_round(
-                    ongoing.max_b_age,
-                    ongoing.max_c_rounds,
-                    ongoing.max_rounds,
-                );
+    /// What tells the states of this instance apart as far as the protocol is concerned,
+    /// leaving out when the rumors were received.
+    #[cfg(test)]
+    pub(crate) fn fingerprint(&self) -> String {
+        let rumors: Vec<_> = self
+            .rumors
+            .iter()
+            .map(|(id, ongoing)| {
+                let informed: Vec<_> = self.indices.ids(&ongoing.informed).collect();
+                let oblivious: Vec<_> = self.indices.ids(&ongoing.oblivious).collect();
+                (id, &ongoing.state, informed, oblivious)
+            })
+            .collect();
+        format!("{:?} {:?}", rumors, self.new_rumors)
+    }
 
-                if ongoing.state == State::D {
-                    return;
+    /// Panics if the progress of any rumor breaks the invariants the protocol relies on,
+    /// whatever the gossips received.
+    #[cfg(any(test, feature = "fuzzing"))]
+    pub(crate) fn check_invariants(&self) {
+        for (id, ongoing) in &self.rumors {
+            assert_eq!(
+                *id, ongoing.content.value,
+                "rumor held under the wrong hash"
+            );
+            assert!(
+                ongoing.informed.is_disjoint(&ongoing.oblivious),
+                "player both informed and oblivious"
+            );
+            assert!(ongoing.state.is_plausible(), "{:?}", ongoing.state);
+            match ongoing.state {
+                State::B { round, .. } => assert!(round < ongoing.max_rounds),
+                State::C {
+                    rounds_in_state_b,
+                    round,
+                } => {
+                    assert!(round < ongoing.max_c_rounds);
+                    assert!(rounds_in_state_b + round < ongoing.max_rounds);
                 }
-
-                let exists = ongoing
-                    .oblivious_players
-                    .iter()
-                    .copied()
-                    .find(|c| c.id == player.id);
-
-                let callee = match exists {
-                    Some(c) => c,
-                    None => return,
-                };
-
-                let rumor = Rumor {
-                    content: ongoing.content.clone(),
-                    callee,
-                    state: ongoing.state.clone(),
-                    caller: InformedPlayer { id: our_id },
-                };
-
-                gossip.rumors.push(rumor);
-
-                // Move the player from Oblivious to Informed.
-                ongoing.oblivious_players = ongoing
-                    .oblivious_players
-                    .iter()
-                    .filter(|c| c.id != callee.id)
-                    .copied()
-                    .collect();
-                ongoing
-                    .informed_players
-                    .push(InformedPlayer { id: callee.id });
-            });
-
-            if !gossip.rumors.is_empty() {
-                return Some(gossip);
+                State::D => (),
             }
         }
-        None
     }
+}
 
-    fn hash(&mut self, content: Content) -> Vec<u8> {
-        content.value // todo
-    }
+/// The age at which a rumor spread to `cluster_size` players moves on from state B, and the
+/// most rounds it's given, in state C or in all.
+fn round_limits(cluster_size: usize) -> (Age, Round) {
+    let cluster_size = cluster_size as f64;
+    let max_b_age = Age::from(cmp::max(1, cluster_size.ln().ceil() as u8));
+    let max_rounds = Round::from(cmp::max(1, cluster_size.ln().ln().ceil() as u8));
+    (max_b_age, max_rounds)
 }
 
+#[derive(Clone)]
 pub struct RumorProgress {
     content: Content,
-    informed_players: Vec<InformedPlayer>,
-    oblivious_players: Vec<ObliviousPlayer>,
+    // The players, by their indices, we've pushed the rumor to or received it from.
+    informed: PlayerSet,
+    // The players, by their indices, we believe yet to learn about the rumor.
+    oblivious: PlayerSet,
     state: State,
+    // When we first saw this rumor.
+    received: Instant,
+    // Whether we initiated this rumor, so that no player can have had it for longer than us.
+    initiated: bool,
     // When in state B, if our age for a Rumor is incremented to this value, the state
     // transitions to C.  Specified in the paper as `O(ln ln n)`.
     max_b_age: Age,
@@ -355,6 +743,21 @@ pub struct RumorProgress {
     max_rounds: Round,
 }
 
+impl RumorProgress {
+    // Whether we initiated the rumor fewer than `max_b_age` rounds ago.
+    fn is_too_young_for_state_c(&self) -> bool {
+        let rounds = match self.state {
+            State::B { round, .. } => round,
+            State::C {
+                rounds_in_state_b,
+                round,
+            } => rounds_in_state_b + round,
+            State::D => return false,
+        };
+        self.initiated && rounds.value < self.max_b_age.value
+    }
+}
+
 impl Default for Gossiping {
     fn default() -> Self {
         let mut rng = rand::thread_rng();
@@ -367,6 +770,8 @@ impl Default for Gossiping {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::gossip::Priority;
+    use crate::state::AgeRule;
     use itertools::Itertools;
     use rand::{self, Rng};
     use std::collections::BTreeMap;
@@ -400,7 +805,7 @@ mod tests {
             let mut raw = [0u8; 20];
             rng.fill(&mut raw[..]);
             let raw_content = String::from_utf8_lossy(&raw).as_bytes().to_vec();
-            rumors.push(Content { value: raw_content });
+            rumors.push(Content::new(raw_content));
         }
 
         let mut rounds = 0;
@@ -437,7 +842,7 @@ mod tests {
                     assert!(&v
                         .get_mut(&dst_id)
                         .unwrap()
-                        .receive_gossip(&pull_msg, false)
+                        .receive_gossip(pull_msg, false)
                         .is_none());
                 }
             }
@@ -456,4 +861,496 @@ mod tests {
             rounds, nodes_missed
         );
     }
+
+    // A cluster of `size` players, all knowing each other.  Built from the ids at once, as
+    // adding thousands of players one by one takes long.
+    fn create_large_network(size: usize) -> Vec<Gossiping> {
+        let players: BTreeSet<Player> = std::iter::repeat_with(|| Player {
+            id: Id(rand::random()),
+        })
+        .take(size)
+        .collect();
+        players
+            .iter()
+            .map(|player| {
+                let mut others = players.clone();
+                let _ = others.remove(player);
+                Gossiping::new(player.id, others)
+            })
+            .collect()
+    }
+
+    // Spreads a rumor from the first player of `network`, alongside its last `faulty` players
+    // which know it too and report it as being in state C in all they send.  Returns how many
+    // honest players got the rumor, and how many were driven to state C.
+    fn spread_with_faulty(
+        network: &[Gossiping],
+        faulty: usize,
+        age_rule: AgeRule,
+    ) -> (usize, usize) {
+        let honest = network.len() - faulty;
+        let mut gossipers = network.to_vec();
+        for gossiper in &mut gossipers {
+            gossiper.config.age_rule = age_rule;
+        }
+        let index: BTreeMap<Id, usize> = gossipers
+            .iter()
+            .enumerate()
+            .map(|(i, gossiper)| (gossiper.our_id(), i))
+            .collect();
+        let tamper = |i: usize, mut gossip: Gossip| {
+            if i >= honest {
+                for rumor in &mut gossip.rumors {
+                    rumor.state = State::C {
+                        rounds_in_state_b: Round::default(),
+                        round: Round::default(),
+                    };
+                }
+            }
+            gossip
+        };
+        let content = Content::new(vec![1; 10]);
+        unwrap!(gossipers[0].initiate_rumor(content.clone()));
+        for gossiper in &mut gossipers[honest..] {
+            unwrap!(gossiper.initiate_rumor(content.clone()));
+        }
+
+        let mut in_state_c = BTreeSet::new();
+        for _ in 0..1000 {
+            // Those with no active rumor have nothing to push.
+            let pushes = gossipers
+                .iter_mut()
+                .enumerate()
+                .filter(|(_, gossiper)| gossiper.has_active_rumors())
+                .filter_map(|(i, gossiper)| Some((i, tamper(i, gossiper.collect_gossip()?))))
+                .collect_vec();
+            if pushes.is_empty() {
+                break;
+            }
+            for (src, push) in pushes {
+                let dst = index[&push.callee.id];
+                if let Some(response) = gossipers[dst].receive_gossip(&push, true) {
+                    let _ = gossipers[src].receive_gossip(&tamper(dst, response), false);
+                }
+            }
+            for (i, gossiper) in gossipers[..honest].iter().enumerate() {
+                if let Some(State::C { .. }) = gossiper.rumors().values().next().map(|r| &r.state) {
+                    let _ = in_state_c.insert(i);
+                }
+            }
+        }
+        let informed = gossipers[..honest]
+            .iter()
+            .filter(|gossiper| !gossiper.rumors().is_empty())
+            .count();
+        (informed, in_state_c.len())
+    }
+
+    #[test]
+    fn faulty_players_are_tolerated() {
+        // From 1619 players on, rumors are given three rounds, in which they reach most of the
+        // cluster.  Honest players still can't reach state C on their own within them, so any
+        // that do were driven there by the faulty players.
+        let network = create_large_network(1700);
+        let faulty = 3;
+        let honest = network.len() - faulty;
+        let (baseline, in_state_c) = spread_with_faulty(&network, 0, AgeRule::Median);
+        println!(
+            "no faulty players: {} of {} players informed",
+            baseline,
+            network.len()
+        );
+        assert_eq!(in_state_c, 0);
+        assert!(baseline * 2 > network.len());
+
+        let (informed, in_state_c) = spread_with_faulty(&network, faulty, AgeRule::Median);
+        println!(
+            "median rule: {} of {} honest players informed, {} driven to state C",
+            informed, honest, in_state_c
+        );
+        assert!(in_state_c > 0);
+
+        // The byzantine rule keeps every honest player out of state C, and the rumor reaches
+        // at least 90% of the share of honest players it reaches with no faulty ones.
+        for max_faulty in faulty..faulty + 2 {
+            let rule = AgeRule::Byzantine { max_faulty };
+            let (informed, in_state_c) = spread_with_faulty(&network, faulty, rule);
+            println!(
+                "byzantine rule tolerating {}: {} of {} honest players informed",
+                max_faulty, informed, honest
+            );
+            assert_eq!(in_state_c, 0);
+            assert!(informed * network.len() * 10 >= baseline * honest * 9);
+        }
+    }
+
+    #[test]
+    fn state_c_is_implausible_for_our_young_rumors() {
+        let gossipers = create_network(20);
+        let mut gossipers = gossipers.into_iter();
+        let (mut us, other) = (unwrap!(gossipers.next()), unwrap!(gossipers.next()));
+        let content = Content::new(vec![1; 10]);
+        unwrap!(us.initiate_rumor(content.clone()));
+        let (callee, caller) = (us.our_id(), other.our_id());
+        let gossip = |state: State| Gossip {
+            callee: ObliviousPlayer { id: callee },
+            rumors: vec![Rumor {
+                content: content.clone(),
+                callee: ObliviousPlayer { id: callee },
+                state,
+                caller: InformedPlayer { id: caller },
+            }],
+            caller: InformedPlayer { id: caller },
+        };
+        let state_c = State::C {
+            rounds_in_state_b: Round::default(),
+            round: Round::default(),
+        };
+        assert!(us.is_plausible(&gossip(State::new())));
+        assert!(!us.is_plausible(&gossip(state_c.clone())));
+        // The players who didn't initiate it can't tell how long it has been going for.
+        assert!(other.is_plausible(&gossip(state_c.clone())));
+
+        // Once the rumor had the rounds to get there, it may well be in state C.
+        let ongoing = unwrap!(us.rumors.get_mut(&content.value));
+        ongoing.state = State::B {
+            round: Round::from(ongoing.max_b_age.value),
+            age: Age::from(1),
+            player_ages: BTreeMap::new(),
+        };
+        assert!(us.is_plausible(&gossip(state_c)));
+    }
+
+    // Clusters of fewer than 16 players give rumors a single round, in which they must still be
+    // pushed, and sent back in the response to a push.
+    #[test]
+    fn small_clusters_spread_rumors() {
+        for size in 2..=17 {
+            let mut gossipers = create_network(size);
+            unwrap!(gossipers[0].initiate_rumor(Content::new(vec![1; 10])));
+            let push = unwrap!(gossipers[0].collect_gossip());
+            let callee = unwrap!(gossipers
+                .iter_mut()
+                .find(|gossiper| gossiper.our_id() == push.callee.id));
+            unwrap!(callee.initiate_rumor(Content::new(vec![2; 10])));
+            let response = unwrap!(callee.receive_gossip(&push, true));
+            assert_eq!(callee.take_new_rumors().len(), 1);
+            assert!(response
+                .rumors
+                .iter()
+                .any(|rumor| rumor.content.value == vec![2; 10]));
+        }
+    }
+
+    #[test]
+    fn bootstrap_new_player() {
+        let mut gossipers = create_network(5);
+        let contents: Vec<Content> = (0..3u8).map(|i| Content::new(vec![i; 10])).collect();
+        for content in &contents {
+            unwrap!(gossipers[0].initiate_rumor(content.clone()));
+        }
+
+        // Nobody but the players of the group is told its rumors.
+        let mut joiner = Gossiping::default();
+        assert!(gossipers[0]
+            .receive_bootstrap_request(joiner.our_id())
+            .is_none());
+        unwrap!(gossipers[0].add_player(joiner.our_id()));
+        let bootstrap = unwrap!(gossipers[0].receive_bootstrap_request(joiner.our_id()));
+        assert_eq!(bootstrap.rumors.len(), contents.len());
+
+        // Only the answers to our requests are accepted.
+        match joiner.receive_bootstrap(&bootstrap) {
+            Err(Error::UnsolicitedBootstrap) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert!(joiner.take_new_rumors().is_empty());
+        joiner.request_bootstrap(gossipers[0].our_id());
+        unwrap!(joiner.receive_bootstrap(&bootstrap));
+
+        let received = joiner.take_new_rumors();
+        assert_eq!(received.len(), contents.len());
+        assert!(joiner.take_new_rumors().is_empty());
+
+        // Receiving the same rumors again doesn't deliver them twice.
+        joiner.request_bootstrap(gossipers[0].our_id());
+        unwrap!(joiner.receive_bootstrap(&bootstrap));
+        assert!(joiner.take_new_rumors().is_empty());
+        assert!(joiner.collect_gossip().is_none());
+
+        // Each request is answered once.
+        assert!(joiner.receive_bootstrap(&bootstrap).is_err());
+    }
+
+    #[test]
+    fn expired_rumors_are_dropped() {
+        let mut gossipers = create_network(2);
+        let expired = Content {
+            deadline: Some(std::time::SystemTime::now() - std::time::Duration::from_secs(10)),
+            ..Content::new(vec![1; 10])
+        };
+        unwrap!(gossipers[0].initiate_rumor(expired.clone()));
+        assert!(gossipers[0].collect_gossip().is_none());
+        assert!(gossipers[0].rumors().is_empty());
+
+        // Expired rumors are refused on receipt too.
+        let gossip = Gossip {
+            callee: ObliviousPlayer {
+                id: gossipers[1].our_id(),
+            },
+            rumors: vec![Rumor {
+                content: expired,
+                callee: ObliviousPlayer {
+                    id: gossipers[1].our_id(),
+                },
+                state: State::new(),
+                caller: InformedPlayer {
+                    id: gossipers[0].our_id(),
+                },
+            }],
+            caller: InformedPlayer {
+                id: gossipers[0].our_id(),
+            },
+        };
+        assert!(gossipers[1].receive_gossip(&gossip, true).is_none());
+        assert!(gossipers[1].rumors().is_empty());
+        assert!(gossipers[1].take_new_rumors().is_empty());
+    }
+
+    #[test]
+    fn rotated_players_keep_their_place() {
+        let mut gossipers = create_network(20);
+        unwrap!(gossipers[0].initiate_rumor(Content::new(vec![1; 10])));
+        let old = gossipers[1].our_id();
+        let new = Gossiping::default().our_id();
+        for gossiper in &mut gossipers {
+            unwrap!(gossiper.rotate_player(old, new));
+        }
+        assert_eq!(gossipers[1].our_id(), new);
+        assert!(gossipers[0].players().contains(&Player { id: new }));
+        assert!(!gossipers[0].players().contains(&Player { id: old }));
+        let (_, oblivious) = gossipers[0].players_of(&[1; 10]);
+        assert!(oblivious.contains(&new));
+        assert!(!oblivious.contains(&old));
+
+        // The same rotation can't be applied twice, nor onto a known player.
+        assert!(gossipers[0].rotate_player(old, new).is_err());
+        let other = gossipers[2].our_id();
+        assert!(gossipers[0].rotate_player(new, other).is_err());
+    }
+
+    #[test]
+    fn indices_are_reused_once_free() {
+        let mut gossiping = Gossiping::default();
+        let removed = Id([1; 32]);
+        unwrap!(gossiping.add_player(removed));
+        unwrap!(gossiping.initiate_rumor(Content::new(vec![1])));
+        gossiping.remove_player(removed);
+
+        // The index of the removed player is kept while the rumor refers to it.
+        for byte in 2..100 {
+            let id = Id([byte; 32]);
+            unwrap!(gossiping.add_player(id));
+            assert_eq!(gossiping.indices.get(&id), Some(1));
+            gossiping.remove_player(id);
+        }
+        assert_eq!(gossiping.indices.get(&removed), Some(0));
+        gossiping.rumors.clear();
+        unwrap!(gossiping.add_player(Id([100; 32])));
+        assert_eq!(gossiping.indices.get(&Id([100; 32])), Some(0));
+        assert_eq!(gossiping.indices.get(&removed), None);
+    }
+
+    #[test]
+    fn quarantined_players_are_not_pushed_to() {
+        let mut gossipers = create_network(20);
+        let released = gossipers[1].our_id();
+        let others: Vec<Id> = gossipers[2..].iter().map(Gossiping::our_id).collect();
+        for id in others {
+            gossipers[0].quarantine(id);
+        }
+        unwrap!(gossipers[0].initiate_rumor(Content::new(vec![1; 10])));
+        let gossip = unwrap!(gossipers[0].collect_gossip());
+        assert_eq!(gossip.callee.id, released);
+
+        // Once all are quarantined, there is no one left to push to.
+        gossipers[0].quarantine(released);
+        unwrap!(gossipers[0].initiate_rumor(Content::new(vec![2; 10])));
+        assert!(gossipers[0].collect_gossip().is_none());
+        gossipers[0].release(released);
+        unwrap!(gossipers[0].initiate_rumor(Content::new(vec![3; 10])));
+        assert_eq!(unwrap!(gossipers[0].collect_gossip()).callee.id, released);
+    }
+
+    #[test]
+    fn rumors_are_credited_to_the_sender() {
+        let mut gossipers = create_network(20);
+        unwrap!(gossipers[1].initiate_rumor(Content::new(vec![1; 10])));
+        let mut gossip = unwrap!(gossipers[1].collect_gossip());
+        // The rumor names another caller than the player the gossip comes from.
+        let sender = gossipers[1].our_id();
+        let named = unwrap!(gossipers[2..]
+            .iter()
+            .map(Gossiping::our_id)
+            .find(|id| *id != gossip.callee.id));
+        gossip.rumors[0].caller = InformedPlayer { id: named };
+        let _ = gossipers[0].receive_gossip(&gossip, false);
+
+        let (informed, oblivious) = gossipers[0].players_of(&[1; 10]);
+        assert_eq!(informed, vec![sender]);
+        assert!(!oblivious.contains(&sender));
+        assert!(oblivious.contains(&named));
+    }
+
+    #[test]
+    fn callers_named_by_rumors_report_no_ages() {
+        let mut gossipers = create_network(20);
+        unwrap!(gossipers[1].initiate_rumor(Content::new(vec![1; 10])));
+        let mut gossip = unwrap!(gossipers[1].collect_gossip());
+        let sender = gossipers[1].our_id();
+        let index = unwrap!(gossipers
+            .iter()
+            .position(|g| g.our_id() == gossip.callee.id));
+        let _ = gossipers[index].receive_gossip(&gossip, false);
+
+        // Were the ages credited to the callers the rumors name, a single player could report
+        // state C on behalf of more players than the Byzantine rule tolerates as faulty.
+        let rumor = gossip.rumors[0].clone();
+        let named: Vec<Id> = gossipers
+            .iter()
+            .map(Gossiping::our_id)
+            .filter(|id| *id != sender && *id != gossip.callee.id)
+            .take(2)
+            .collect();
+        gossip.rumors = named
+            .iter()
+            .map(|id| Rumor {
+                state: State::C {
+                    rounds_in_state_b: Round::from(1),
+                    round: Round::default(),
+                },
+                caller: InformedPlayer { id: *id },
+                ..rumor.clone()
+            })
+            .collect();
+        let _ = gossipers[index].receive_gossip(&gossip, false);
+
+        let state = &gossipers[index].rumors[&Bytes::from(vec![1; 10])].state;
+        match state {
+            State::B { player_ages, .. } => {
+                assert_eq!(player_ages.keys().collect_vec(), vec![&sender]);
+                assert_eq!(player_ages[&sender], Age::max());
+            }
+            state => panic!("Unexpected state {:?}", state),
+        }
+    }
+
+    #[test]
+    fn high_priority_rumors_are_sent_first() {
+        let gossipers = create_network(20);
+        let config = Config {
+            max_rumors_per_gossip: Some(2),
+            ..Config::default()
+        };
+        let mut gossiper =
+            Gossiping::with_config(gossipers[0].our_id(), gossipers[0].players.clone(), config);
+        let priorities = [Priority::Bulk, Priority::High, Priority::Normal];
+        for (i, priority) in priorities.iter().enumerate() {
+            let content = Content::new(vec![i as u8; 10]).with_priority(*priority);
+            unwrap!(gossiper.initiate_rumor(content));
+        }
+
+        let gossip = unwrap!(gossiper.collect_gossip());
+        let sent = gossip
+            .rumors
+            .iter()
+            .map(|rumor| rumor.content.priority)
+            .collect_vec();
+        assert_eq!(sent, vec![Priority::High, Priority::Normal]);
+
+        // The bulk rumor was deferred, so the callee is still regarded as oblivious of it.
+        let (_, oblivious) = gossiper.players_of(&[0; 10]);
+        assert!(oblivious.contains(&gossip.callee.id));
+    }
+
+    // Rumors take up more room in CBOR than in bincode, and the byte limit accounts for it.
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn byte_limit_follows_the_codec() {
+        use crate::codec::Codec;
+
+        let gossipers = create_network(20);
+        let sent = |codec| {
+            let config = Config {
+                max_bytes_per_gossip: Some(1000),
+                codec,
+                ..Config::default()
+            };
+            let players = gossipers[0].players.clone();
+            let mut gossiper = Gossiping::with_config(gossipers[0].our_id(), players, config);
+            for i in 0..4u8 {
+                unwrap!(gossiper.initiate_rumor(Content::new(vec![200 + i; 300])));
+            }
+            unwrap!(gossiper.collect_gossip()).rumors.len()
+        };
+        assert_eq!(sent(Codec::Bincode), 2);
+        assert_eq!(sent(Codec::Cbor), 1);
+    }
+
+    #[test]
+    fn topic_rumors_only_reach_subscribers() {
+        let mut gossipers = create_network(20);
+        let topic = Topic("status".to_string());
+        for gossiper in gossipers.iter_mut().skip(10) {
+            gossiper.subscribe(topic.clone());
+        }
+        let advertisements = gossipers
+            .iter()
+            .map(|gossiper| gossiper.subscriptions())
+            .collect_vec();
+        for gossiper in gossipers.iter_mut() {
+            for subscriptions in &advertisements {
+                gossiper.receive_subscriptions(subscriptions.clone());
+            }
+        }
+        let mut stranger = Gossiping::default();
+        stranger.subscribe(topic.clone());
+        gossipers[0].receive_subscriptions(stranger.subscriptions());
+        assert!(!gossipers[0].subscriptions.contains_key(&stranger.our_id()));
+        let subscribers: BTreeSet<Id> = gossipers
+            .iter()
+            .skip(10)
+            .map(|gossiper| gossiper.our_id())
+            .collect();
+
+        let content = Content::new(vec![1; 10]).with_topic(topic);
+        unwrap!(gossipers[0].initiate_rumor(content.clone()));
+        let ongoing = unwrap!(gossipers[0].rumors().get(&content.value));
+        let (_, audience) = gossipers[0].players_of(&content.value);
+        assert_eq!(audience.into_iter().collect::<BTreeSet<_>>(), subscribers);
+        assert_eq!(ongoing.max_b_age, Age::from(3)); // ceil(ln(10))
+
+        // Players not subscribed to the topic ignore its rumors.
+        let gossip = Gossip {
+            callee: ObliviousPlayer {
+                id: gossipers[1].our_id(),
+            },
+            rumors: vec![Rumor {
+                content,
+                callee: ObliviousPlayer {
+                    id: gossipers[1].our_id(),
+                },
+                state: State::new(),
+                caller: InformedPlayer {
+                    id: gossipers[0].our_id(),
+                },
+            }],
+            caller: InformedPlayer {
+                id: gossipers[0].our_id(),
+            },
+        };
+        let _ = gossipers[1].receive_gossip(&gossip, false);
+        assert!(gossipers[1].rumors().is_empty());
+        assert!(gossipers[1].take_new_rumors().is_empty());
+    }
 }
@@ -30,3 +30,7 @@ impl Debug for Id {
         )
     }
 }
+
+/// The name of a gossip group, i.e. of one of the clusters a node takes part in.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct GroupId(pub String);
@@ -0,0 +1,333 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! The identities of players, and the signature schemes they come with.
+//!
+//! Players are known to the gossip by their `Id`, which is derived from their `PublicId`.  A
+//! node signs its transmissions with its `SecretId`, and the recipient verifies them with the
+//! `PublicId` of the sender as given by the transport.
+
+use crate::error::Error;
+use crate::id::Id;
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use curve25519_dalek::scalar::Scalar;
+use ed25519_dalek::{ExpandedSecretKey, Keypair, PublicKey, Signature};
+use sha3::{Digest, Sha3_256, Sha3_512};
+use std::fmt::Debug;
+
+/// The public identity of a player, which its signatures are verified with.
+pub trait PublicId: Clone + Debug + Send + 'static {
+    /// The encoding of the identity.
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// Decodes an identity encoded by `to_bytes`.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, Error>
+    where
+        Self: Sized;
+
+    /// The `Id` the player is known by.  Defaults to the SHA3-256 hash of the encoding.
+    fn id(&self) -> Id {
+        let mut id = [0; 32];
+        id.copy_from_slice(&Sha3_256::digest(&self.to_bytes()));
+        Id(id)
+    }
+
+    /// Checks that `sig` was made over `data` by the owner of this identity.
+    fn verify(&self, data: &[u8], sig: &[u8]) -> Result<(), Error>;
+
+    /// Checks a batch of signatures, returning the outcome for each in order.
+    fn verify_batch(batch: &[(&Self, &[u8], &[u8])]) -> Vec<Result<(), Error>>
+    where
+        Self: Sized,
+    {
+        batch
+            .iter()
+            .map(|(public_id, data, sig)| public_id.verify(data, sig))
+            .collect()
+    }
+}
+
+/// The secret identity of this node, which it signs with.
+pub trait SecretId: Send + 'static {
+    /// The public counterpart of this identity.
+    type Public: PublicId;
+
+    /// Returns the public counterpart of this identity.
+    fn public_id(&self) -> Self::Public;
+
+    /// Signs `data`.
+    fn sign(&self, data: &[u8]) -> Vec<u8>;
+
+    /// Returns the secret we share with the owner of `peer`, who gets the same from its own
+    /// secret identity and our public one.  Schemes without key agreement can't be used in
+    /// groups requiring encryption.
+    fn shared_secret(&self, _peer: &Self::Public) -> Result<[u8; 32], Error> {
+        Err(Error::KeyAgreementFailure)
+    }
+}
+
+impl PublicId for PublicKey {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        PublicKey::from_bytes(bytes).map_err(|error| Error::Encoding(error.to_string()))
+    }
+
+    /// The public key itself, which is how players have always been known.
+    fn id(&self) -> Id {
+        Id::from(*self)
+    }
+
+    fn verify(&self, data: &[u8], sig: &[u8]) -> Result<(), Error> {
+        let sig = Signature::from_bytes(sig).map_err(|_| Error::SigFailure)?;
+        PublicKey::verify::<Sha3_512>(self, data, &sig).map_err(|_| Error::SigFailure)
+    }
+
+    /// Verifies the whole batch at once, which is cheaper than one by one.  Only if that fails
+    /// are the signatures verified one by one, to tell which are bad.
+    fn verify_batch(batch: &[(&Self, &[u8], &[u8])]) -> Vec<Result<(), Error>> {
+        let sigs: Result<Vec<Signature>, _> = batch
+            .iter()
+            .map(|(_, _, sig)| Signature::from_bytes(sig))
+            .collect();
+        if let (true, Ok(sigs)) = (batch.len() > 1, sigs) {
+            let messages: Vec<&[u8]> = batch.iter().map(|(_, data, _)| *data).collect();
+            let keys: Vec<PublicKey> = batch.iter().map(|(key, _, _)| **key).collect();
+            if ed25519_dalek::verify_batch::<Sha3_512>(&messages, &sigs, &keys).is_ok() {
+                return batch.iter().map(|_| Ok(())).collect();
+            }
+        }
+        batch
+            .iter()
+            .map(|(key, data, sig)| PublicId::verify(*key, data, sig))
+            .collect()
+    }
+}
+
+impl SecretId for Keypair {
+    type Public = PublicKey;
+
+    fn public_id(&self) -> PublicKey {
+        self.public
+    }
+
+    fn sign(&self, data: &[u8]) -> Vec<u8> {
+        Keypair::sign::<Sha3_512>(self, data).to_bytes().to_vec()
+    }
+
+    /// X25519, with both keys converted from ed25519.
+    fn shared_secret(&self, peer: &PublicKey) -> Result<[u8; 32], Error> {
+        let scalar = ExpandedSecretKey::from_secret_key::<Sha3_512>(&self.secret).to_bytes();
+        let mut bits = [0; 32];
+        bits.copy_from_slice(&scalar[..32]);
+        let point = CompressedEdwardsY(peer.to_bytes())
+            .decompress()
+            .ok_or(Error::KeyAgreementFailure)?
+            .to_montgomery();
+        let shared = point * Scalar::from_bits(bits);
+        // A low order point yields a predictable secret.
+        if shared.as_bytes().iter().all(|&byte| byte == 0) {
+            return Err(Error::KeyAgreementFailure);
+        }
+        Ok(shared.to_bytes())
+    }
+}
+
+#[cfg(feature = "bls")]
+pub use self::bls::{BlsPublicId, BlsSecretId};
+
+#[cfg(feature = "bls")]
+mod bls {
+    use super::{PublicId, SecretId};
+    use crate::error::Error;
+    use blst::min_pk::{PublicKey, SecretKey, Signature};
+    use blst::BLST_ERROR;
+    use rand::RngCore;
+
+    // The ciphersuite of the IETF BLS signature draft, for the basic scheme.
+    const DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_";
+
+    /// A BLS public key, on G1.
+    #[derive(Clone, Debug)]
+    pub struct BlsPublicId(PublicKey);
+
+    impl PublicId for BlsPublicId {
+        fn to_bytes(&self) -> Vec<u8> {
+            self.0.compress().to_vec()
+        }
+
+        fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+            PublicKey::key_validate(bytes)
+                .map(BlsPublicId)
+                .map_err(|error| Error::Encoding(format!("{:?}", error)))
+        }
+
+        fn verify(&self, data: &[u8], sig: &[u8]) -> Result<(), Error> {
+            let sig = Signature::uncompress(sig).map_err(|_| Error::SigFailure)?;
+            match sig.verify(true, data, DST, &[], &self.0, true) {
+                BLST_ERROR::BLST_SUCCESS => Ok(()),
+                _ => Err(Error::SigFailure),
+            }
+        }
+    }
+
+    /// A BLS secret key, signing on G2.
+    pub struct BlsSecretId(SecretKey);
+
+    impl BlsSecretId {
+        /// Derives a secret key from at least 32 bytes of keying material.
+        pub fn from_seed(seed: &[u8]) -> Result<Self, Error> {
+            SecretKey::key_gen(seed, &[])
+                .map(BlsSecretId)
+                .map_err(|error| Error::Encoding(format!("{:?}", error)))
+        }
+
+        /// Generates a random secret key.
+        pub fn generate() -> Self {
+            let mut seed = [0; 32];
+            loop {
+                rand::thread_rng().fill_bytes(&mut seed);
+                if let Ok(secret_id) = Self::from_seed(&seed) {
+                    return secret_id;
+                }
+            }
+        }
+    }
+
+    impl SecretId for BlsSecretId {
+        type Public = BlsPublicId;
+
+        fn public_id(&self) -> BlsPublicId {
+            BlsPublicId(self.0.sk_to_pk())
+        }
+
+        fn sign(&self, data: &[u8]) -> Vec<u8> {
+            self.0.sign(data, DST, &[]).compress().to_vec()
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use unwrap::unwrap;
+
+    /// An identity which is just a number, with signatures anyone can forge.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub(crate) struct TestId(pub u64);
+
+    impl TestId {
+        fn sig(&self, data: &[u8]) -> Vec<u8> {
+            let mut sig = self.0.to_be_bytes().to_vec();
+            sig.extend_from_slice(&Sha3_256::digest(data));
+            sig
+        }
+    }
+
+    impl PublicId for TestId {
+        fn to_bytes(&self) -> Vec<u8> {
+            self.0.to_be_bytes().to_vec()
+        }
+
+        fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+            let mut id = [0; 8];
+            if bytes.len() != id.len() {
+                return Err(Error::Encoding("invalid test id".to_string()));
+            }
+            id.copy_from_slice(bytes);
+            Ok(TestId(u64::from_be_bytes(id)))
+        }
+
+        fn verify(&self, data: &[u8], sig: &[u8]) -> Result<(), Error> {
+            if self.sig(data) == sig {
+                Ok(())
+            } else {
+                Err(Error::SigFailure)
+            }
+        }
+    }
+
+    impl SecretId for TestId {
+        type Public = TestId;
+
+        fn public_id(&self) -> TestId {
+            *self
+        }
+
+        fn sign(&self, data: &[u8]) -> Vec<u8> {
+            self.sig(data)
+        }
+
+        fn shared_secret(&self, peer: &TestId) -> Result<[u8; 32], Error> {
+            let mut secret = [0; 32];
+            secret[..8].copy_from_slice(&(self.0 ^ peer.0).to_be_bytes());
+            Ok(secret)
+        }
+    }
+
+    fn check_scheme<S: SecretId>(secret_id: &S, other: &S) {
+        let public_id = secret_id.public_id();
+        let decoded = unwrap!(S::Public::from_bytes(&public_id.to_bytes()));
+        assert_eq!(decoded.id(), public_id.id());
+        let sig = secret_id.sign(b"data");
+        assert!(public_id.verify(b"data", &sig).is_ok());
+        assert!(public_id.verify(b"other data", &sig).is_err());
+        assert!(other.public_id().verify(b"data", &sig).is_err());
+        assert_ne!(public_id.id(), other.public_id().id());
+    }
+
+    #[test]
+    fn schemes() {
+        let mut rng = rand::thread_rng();
+        let keys = Keypair::generate::<Sha3_512, _>(&mut rng);
+        let other_keys = Keypair::generate::<Sha3_512, _>(&mut rng);
+        check_scheme(&keys, &other_keys);
+        assert_eq!(keys.public.id(), Id::from(keys.public));
+        assert_eq!(
+            unwrap!(keys.shared_secret(&other_keys.public)),
+            unwrap!(other_keys.shared_secret(&keys.public))
+        );
+
+        check_scheme(&TestId(1), &TestId(2));
+
+        #[cfg(feature = "bls")]
+        check_scheme(&BlsSecretId::generate(), &BlsSecretId::generate());
+    }
+
+    #[test]
+    fn batch_verification() {
+        let mut rng = rand::thread_rng();
+        let keys: Vec<_> = (0..8)
+            .map(|_| Keypair::generate::<Sha3_512, _>(&mut rng))
+            .collect();
+        let data = b"transmission";
+        let mut sigs: Vec<_> = keys.iter().map(|keys| SecretId::sign(keys, data)).collect();
+        let batch = |sigs: &[Vec<u8>]| -> Vec<bool> {
+            let batch: Vec<_> = keys
+                .iter()
+                .zip(sigs)
+                .map(|(keys, sig)| (&keys.public, &data[..], &sig[..]))
+                .collect();
+            PublicKey::verify_batch(&batch)
+                .iter()
+                .map(Result::is_ok)
+                .collect()
+        };
+        assert!(batch(&sigs).iter().all(|&ok| ok));
+
+        sigs.swap(2, 5);
+        let verified = batch(&sigs);
+        assert_eq!(
+            verified,
+            vec![true, true, false, true, true, false, true, true]
+        );
+    }
+}
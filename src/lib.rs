@@ -15,13 +15,13 @@
     html_root_url = "https://docs.rs/safe-gossip"
 )]
 #![forbid(
-    exceeding_bitshifts,
+    arithmetic_overflow,
     mutable_transmutes,
     no_mangle_const_items,
-    unknown_crate_types,
-    warnings
+    unknown_crate_types
 )]
 #![deny(
+    warnings,
     bad_style,
     deprecated,
     improper_ctypes,
@@ -49,7 +49,6 @@
     unused_results
 )]
 #![allow(
-    box_pointers,
     missing_copy_implementations,
     missing_debug_implementations,
     unused_extern_crates,
@@ -69,18 +68,51 @@ extern crate quick_error;
 #[macro_use]
 extern crate unwrap;
 
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench;
+mod codec;
+mod compression;
+mod config;
+mod encryption;
 mod error;
+#[cfg(test)]
+mod explorer;
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub mod fuzzing;
 mod gossip;
 mod gossip_stepper;
 mod gossiping;
 mod id;
+mod identity;
+mod player_set;
+#[cfg(feature = "protobuf")]
+mod protobuf;
+mod rate_limit;
+mod replay;
+mod reputation;
+mod signing;
 mod state;
 mod transmission;
 
+pub use crate::codec::Codec;
+pub use crate::compression::{Compression, CompressionMetrics};
+pub use crate::config::{Config, Limits};
+pub use crate::encryption::Encryption;
 pub use crate::error::Error;
-pub use crate::gossip::{Content, Player};
+pub use crate::gossip::{Content, Player, Priority, Subscriptions, Topic};
 pub use crate::gossip_stepper::{
     ClientChannel, ClientCmd, GossipStepper, PlayerIncomingChannel, PlayerOutgoingChannels,
+    SendError,
 };
 pub use crate::gossiping::Gossiping;
-pub use crate::id::Id;
+pub use crate::id::{GroupId, Id};
+#[cfg(feature = "bls")]
+pub use crate::identity::{BlsPublicId, BlsSecretId};
+pub use crate::identity::{PublicId, SecretId};
+pub use crate::rate_limit::{Overflow, RateLimitConfig};
+pub use crate::reputation::{Misbehaviour, QuarantineEvent, ReputationConfig};
+pub use crate::signing::{NativeSigner, NullSigner, Signer, Verifier};
+pub use crate::state::AgeRule;
+pub use crate::transmission::{MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
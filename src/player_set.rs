@@ -0,0 +1,184 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use crate::id::Id;
+use std::collections::{BTreeMap, BTreeSet};
+
+const BITS: usize = 64;
+
+/// The stable indices of the players, by which the players of each rumor are held in bitsets.
+/// A player keeps its index once removed, so that a rumor still regards it the same way if it's
+/// added back, and a player rotating its key keeps its index under its new id.  Indices no
+/// longer used are given to new players, so that they stay below the number of players and of
+/// removed players some rumor still refers to.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PlayerIndices {
+    indices: BTreeMap<Id, usize>,
+    // The indices given up, which are given again before any new one.
+    free: BTreeSet<usize>,
+    next: usize,
+}
+
+impl PlayerIndices {
+    /// The index of `id`, if it was ever given one.
+    pub(crate) fn get(&self, id: &Id) -> Option<usize> {
+        self.indices.get(id).copied()
+    }
+
+    /// The index of `id`, giving it the lowest free one if it has none yet.
+    pub(crate) fn insert(&mut self, id: Id) -> usize {
+        let (free, next) = (&mut self.free, &mut self.next);
+        *self.indices.entry(id).or_insert_with(|| {
+            free.pop_first().unwrap_or_else(|| {
+                *next += 1;
+                *next - 1
+            })
+        })
+    }
+
+    /// Forgets the players whose indices aren't in `used`, freeing their indices.
+    pub(crate) fn retain(&mut self, used: &PlayerSet) {
+        let free = &mut self.free;
+        self.indices.retain(|_, index| {
+            if used.contains(*index) {
+                return true;
+            }
+            let _ = free.insert(*index);
+            false
+        });
+    }
+
+    /// Gives the index of `old` to `new`.
+    pub(crate) fn rotate(&mut self, old: &Id, new: Id) {
+        if let Some(index) = self.indices.remove(old) {
+            let _ = self.indices.insert(new, index);
+        }
+    }
+
+    /// The ids of the players in `set`.
+    pub(crate) fn ids<'a>(&'a self, set: &'a PlayerSet) -> impl Iterator<Item = Id> + 'a {
+        self.indices
+            .iter()
+            .filter(move |(_, index)| set.contains(**index))
+            .map(|(id, _)| *id)
+    }
+}
+
+/// A set of players, as a bitset of their indices.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct PlayerSet {
+    words: Vec<u64>,
+}
+
+impl PlayerSet {
+    /// Adds the player of `index`, returning whether it wasn't in the set yet.
+    pub(crate) fn insert(&mut self, index: usize) -> bool {
+        let (word, bit) = (index / BITS, 1 << (index % BITS));
+        if self.words.len() <= word {
+            self.words.resize(word + 1, 0);
+        }
+        let inserted = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        inserted
+    }
+
+    /// Removes the player of `index`, returning whether it was in the set.
+    pub(crate) fn remove(&mut self, index: usize) -> bool {
+        let (word, bit) = (index / BITS, 1 << (index % BITS));
+        match self.words.get_mut(word) {
+            Some(bits) if *bits & bit != 0 => {
+                *bits &= !bit;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn contains(&self, index: usize) -> bool {
+        self.words
+            .get(index / BITS)
+            .is_some_and(|bits| bits & (1 << (index % BITS)) != 0)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|bits| bits.count_ones() as usize)
+            .sum()
+    }
+
+    /// Adds the players of `other`.
+    pub(crate) fn union_with(&mut self, other: &PlayerSet) {
+        if self.words.len() < other.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (bits, other) in self.words.iter_mut().zip(&other.words) {
+            *bits |= other;
+        }
+    }
+
+    /// Keeps only the players also in `other`.
+    pub(crate) fn intersect_with(&mut self, other: &PlayerSet) {
+        self.words.truncate(other.words.len());
+        for (bits, other) in self.words.iter_mut().zip(&other.words) {
+            *bits &= other;
+        }
+    }
+
+    #[cfg(any(test, feature = "fuzzing"))]
+    pub(crate) fn is_disjoint(&self, other: &PlayerSet) -> bool {
+        self.words
+            .iter()
+            .zip(&other.words)
+            .all(|(bits, other)| bits & other == 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sets_of_indices() {
+        let mut indices = PlayerIndices::default();
+        let ids: Vec<Id> = (0..100).map(|byte| Id([byte; 32])).collect();
+        for (expected, id) in ids.iter().enumerate() {
+            assert_eq!(indices.insert(*id), expected);
+        }
+        assert_eq!(indices.insert(ids[3]), 3);
+
+        let mut set = PlayerSet::default();
+        assert!(set.insert(3) && set.insert(70) && !set.insert(70));
+        assert!(set.contains(70) && !set.contains(69) && !set.contains(1000));
+        assert_eq!(set.len(), 2);
+        assert_eq!(indices.ids(&set).collect::<Vec<_>>(), vec![ids[3], ids[70]]);
+
+        // A rotated player keeps its index, and a new one gets a fresh index.
+        let new = Id([200; 32]);
+        indices.rotate(&ids[70], new);
+        assert_eq!(indices.get(&new), Some(70));
+        assert_eq!(indices.get(&ids[70]), None);
+        assert_eq!(indices.insert(ids[70]), 100);
+
+        // Indices not in use are given again, lowest first.
+        indices.retain(&set);
+        assert_eq!(indices.ids(&set).collect::<Vec<_>>(), vec![ids[3], new]);
+        assert_eq!(indices.get(&ids[4]), None);
+        assert_eq!(indices.insert(ids[4]), 0);
+        assert_eq!(indices.insert(ids[5]), 1);
+
+        let mut other = PlayerSet::default();
+        assert!(other.insert(4) && set.is_disjoint(&other));
+        other.union_with(&set);
+        assert!(!set.is_disjoint(&other) && other.len() == 3);
+        other.intersect_with(&set);
+        assert_eq!(other, set);
+        assert!(other.remove(3) && !other.remove(3) && !other.remove(1000));
+    }
+}
@@ -0,0 +1,594 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! Conversions between the wire types and their protobuf representation, as specified in
+//! `proto/safe_gossip.proto`.
+
+use crate::error::Error;
+use crate::gossip::{
+    Content, Gossip, InformedPlayer, ObliviousPlayer, Priority, Rumor, Subscriptions, Topic,
+};
+use crate::id::{GroupId, Id};
+use crate::state::{Age, Round, State};
+use crate::transmission::{Body, KeyRotation, Versions};
+use ed25519_dalek::PUBLIC_KEY_LENGTH;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A type which is sent over the wire.
+pub trait Wire: Serialize + DeserializeOwned {
+    /// The protobuf representation of the type.
+    type Proto: prost::Message + Default;
+
+    fn to_proto(&self) -> Self::Proto;
+
+    fn from_proto(proto: Self::Proto) -> Result<Self, Error>;
+}
+
+impl Wire for Body {
+    type Proto = proto::Body;
+
+    fn to_proto(&self) -> proto::Body {
+        proto::Body {
+            group: self.group.0.clone(),
+            payload: self.payload.clone(),
+            sig: self.sig.clone(),
+        }
+    }
+
+    fn from_proto(proto: proto::Body) -> Result<Self, Error> {
+        Ok(Body {
+            group: GroupId(proto.group),
+            payload: proto.payload,
+            sig: proto.sig,
+        })
+    }
+}
+
+impl Wire for Id {
+    type Proto = proto::PlayerId;
+
+    fn to_proto(&self) -> proto::PlayerId {
+        proto::PlayerId {
+            id: self.0.to_vec(),
+        }
+    }
+
+    fn from_proto(proto: proto::PlayerId) -> Result<Self, Error> {
+        id_from_bytes(&proto.id)
+    }
+}
+
+impl Wire for Gossip {
+    type Proto = proto::Gossip;
+
+    fn to_proto(&self) -> proto::Gossip {
+        proto::Gossip {
+            callee: self.callee.id.0.to_vec(),
+            rumors: self.rumors.iter().map(rumor_to_proto).collect(),
+            caller: self.caller.id.0.to_vec(),
+        }
+    }
+
+    fn from_proto(proto: proto::Gossip) -> Result<Self, Error> {
+        Ok(Gossip {
+            callee: ObliviousPlayer {
+                id: id_from_bytes(&proto.callee)?,
+            },
+            rumors: proto
+                .rumors
+                .into_iter()
+                .map(rumor_from_proto)
+                .collect::<Result<_, _>>()?,
+            caller: InformedPlayer {
+                id: id_from_bytes(&proto.caller)?,
+            },
+        })
+    }
+}
+
+impl Wire for Subscriptions {
+    type Proto = proto::Subscriptions;
+
+    fn to_proto(&self) -> proto::Subscriptions {
+        proto::Subscriptions {
+            player: self.player.0.to_vec(),
+            topics: self.topics.iter().map(|topic| topic.0.clone()).collect(),
+        }
+    }
+
+    fn from_proto(proto: proto::Subscriptions) -> Result<Self, Error> {
+        Ok(Subscriptions {
+            player: id_from_bytes(&proto.player)?,
+            topics: proto.topics.into_iter().map(Topic).collect(),
+        })
+    }
+}
+
+impl Wire for Versions {
+    type Proto = proto::Versions;
+
+    fn to_proto(&self) -> proto::Versions {
+        proto::Versions {
+            min: u32::from(self.min),
+            max: u32::from(self.max),
+        }
+    }
+
+    fn from_proto(proto: proto::Versions) -> Result<Self, Error> {
+        Ok(Versions {
+            min: u16::try_from(proto.min).map_err(|_| invalid("version"))?,
+            max: u16::try_from(proto.max).map_err(|_| invalid("version"))?,
+        })
+    }
+}
+
+impl Wire for KeyRotation {
+    type Proto = proto::KeyRotation;
+
+    fn to_proto(&self) -> proto::KeyRotation {
+        proto::KeyRotation {
+            public_id: self.public_id.clone(),
+            sig: self.sig.clone(),
+        }
+    }
+
+    fn from_proto(proto: proto::KeyRotation) -> Result<Self, Error> {
+        Ok(KeyRotation {
+            public_id: proto.public_id,
+            sig: proto.sig,
+        })
+    }
+}
+
+pub(crate) fn rumor_to_proto(rumor: &Rumor) -> proto::Rumor {
+    let content = &rumor.content;
+    let priority = match content.priority {
+        Priority::Normal => proto::Priority::Normal,
+        Priority::Bulk => proto::Priority::Bulk,
+        Priority::High => proto::Priority::High,
+    };
+    let state = match &rumor.state {
+        State::B {
+            round,
+            age,
+            player_ages,
+        } => proto::state::Phase::B(proto::PhaseB {
+            round: u32::from(round.value),
+            age: u32::from(age.value),
+            player_ages: player_ages
+                .iter()
+                .map(|(player, age)| proto::PlayerAge {
+                    player: player.0.to_vec(),
+                    age: u32::from(age.value),
+                })
+                .collect(),
+        }),
+        State::C {
+            rounds_in_state_b,
+            round,
+        } => proto::state::Phase::C(proto::PhaseC {
+            rounds_in_state_b: u32::from(rounds_in_state_b.value),
+            round: u32::from(round.value),
+        }),
+        State::D => proto::state::Phase::D(proto::PhaseD {}),
+    };
+    proto::Rumor {
+        content: Some(proto::Content {
+            value: content.value.clone(),
+            deadline: content.deadline.map(|deadline| {
+                let since_epoch = deadline.duration_since(UNIX_EPOCH).unwrap_or_default();
+                proto::Deadline {
+                    secs: since_epoch.as_secs(),
+                    nanos: since_epoch.subsec_nanos(),
+                }
+            }),
+            priority: priority as i32,
+            topic: content.topic.as_ref().map(|topic| proto::Topic {
+                name: topic.0.clone(),
+            }),
+        }),
+        callee: rumor.callee.id.0.to_vec(),
+        state: Some(proto::State { phase: Some(state) }),
+        caller: rumor.caller.id.0.to_vec(),
+    }
+}
+
+fn rumor_from_proto(proto: proto::Rumor) -> Result<Rumor, Error> {
+    let content = proto.content.ok_or_else(|| invalid("content"))?;
+    let priority = match proto::Priority::from_i32(content.priority) {
+        Some(proto::Priority::Normal) => Priority::Normal,
+        Some(proto::Priority::Bulk) => Priority::Bulk,
+        Some(proto::Priority::High) => Priority::High,
+        None => return Err(invalid("priority")),
+    };
+    let phase = proto
+        .state
+        .and_then(|state| state.phase)
+        .ok_or_else(|| invalid("state"))?;
+    let state = match phase {
+        proto::state::Phase::B(b) => State::B {
+            round: Round::from(to_u8(b.round)?),
+            age: Age::from(to_u8(b.age)?),
+            player_ages: b
+                .player_ages
+                .into_iter()
+                .map(|player_age| {
+                    Ok((
+                        id_from_bytes(&player_age.player)?,
+                        Age::from(to_u8(player_age.age)?),
+                    ))
+                })
+                .collect::<Result<BTreeMap<_, _>, Error>>()?,
+        },
+        proto::state::Phase::C(c) => State::C {
+            rounds_in_state_b: Round::from(to_u8(c.rounds_in_state_b)?),
+            round: Round::from(to_u8(c.round)?),
+        },
+        proto::state::Phase::D(_) => State::D,
+    };
+    Ok(Rumor {
+        content: Content {
+            value: content.value,
+            deadline: content.deadline.map(deadline_from_proto).transpose()?,
+            priority,
+            topic: content.topic.map(|topic| Topic(topic.name)),
+        },
+        callee: ObliviousPlayer {
+            id: id_from_bytes(&proto.callee)?,
+        },
+        state,
+        caller: InformedPlayer {
+            id: id_from_bytes(&proto.caller)?,
+        },
+    })
+}
+
+// The deadline the sender gave, if it can be represented at all.
+fn deadline_from_proto(deadline: proto::Deadline) -> Result<SystemTime, Error> {
+    if deadline.nanos >= 1_000_000_000 {
+        return Err(invalid_deadline());
+    }
+    UNIX_EPOCH
+        .checked_add(Duration::new(deadline.secs, deadline.nanos))
+        .ok_or_else(invalid_deadline)
+}
+
+fn invalid_deadline() -> Error {
+    Error::Serialisation(Box::new(bincode::ErrorKind::Custom(
+        "invalid deadline".to_string(),
+    )))
+}
+
+fn id_from_bytes(bytes: &[u8]) -> Result<Id, Error> {
+    if bytes.len() != PUBLIC_KEY_LENGTH {
+        return Err(invalid("player id"));
+    }
+    let mut id = [0; PUBLIC_KEY_LENGTH];
+    id.copy_from_slice(bytes);
+    Ok(Id(id))
+}
+
+fn to_u8(value: u32) -> Result<u8, Error> {
+    u8::try_from(value).map_err(|_| invalid("counter"))
+}
+
+fn invalid(field: &str) -> Error {
+    Error::Encoding(format!("invalid {}", field))
+}
+
+/// The types generated from `proto/safe_gossip.proto`.
+#[allow(missing_docs)]
+pub mod proto {
+    use bytes::Bytes;
+    use prost::bytes::{Buf, BufMut};
+    use prost::encoding::{self, DecodeContext, WireType};
+    use prost::DecodeError;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Body {
+        #[prost(string, tag = "1")]
+        pub group: String,
+        #[prost(bytes, tag = "2")]
+        pub payload: Vec<u8>,
+        #[prost(bytes, tag = "3")]
+        pub sig: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct PlayerId {
+        #[prost(bytes, tag = "1")]
+        pub id: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Gossip {
+        #[prost(bytes, tag = "1")]
+        pub callee: Vec<u8>,
+        #[prost(message, repeated, tag = "2")]
+        pub rumors: Vec<Rumor>,
+        #[prost(bytes, tag = "3")]
+        pub caller: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Rumor {
+        #[prost(message, optional, tag = "1")]
+        pub content: Option<Content>,
+        #[prost(bytes, tag = "2")]
+        pub callee: Vec<u8>,
+        #[prost(message, optional, tag = "3")]
+        pub state: Option<State>,
+        #[prost(bytes, tag = "4")]
+        pub caller: Vec<u8>,
+    }
+
+    // Implemented by hand rather than derived, as prost only derives `bytes` fields as
+    // `Vec<u8>`, which would copy every value when encoding.
+    #[derive(Clone, Debug, Default, PartialEq)]
+    pub struct Content {
+        pub value: Bytes,
+        pub deadline: Option<Deadline>,
+        pub priority: i32,
+        pub topic: Option<Topic>,
+    }
+
+    impl prost::Message for Content {
+        fn encode_raw<B: BufMut>(&self, buf: &mut B) {
+            if !self.value.is_empty() {
+                encoding::encode_key(1, WireType::LengthDelimited, buf);
+                encoding::encode_varint(self.value.len() as u64, buf);
+                buf.put_slice(&self.value);
+            }
+            if let Some(deadline) = &self.deadline {
+                encoding::message::encode(2, deadline, buf);
+            }
+            if self.priority != Priority::default() as i32 {
+                encoding::int32::encode(3, &self.priority, buf);
+            }
+            if let Some(topic) = &self.topic {
+                encoding::message::encode(4, topic, buf);
+            }
+        }
+
+        fn merge_field<B: Buf>(
+            &mut self,
+            tag: u32,
+            wire_type: WireType,
+            buf: &mut B,
+            ctx: DecodeContext,
+        ) -> Result<(), DecodeError> {
+            match tag {
+                1 => {
+                    let mut value = vec![];
+                    encoding::bytes::merge(wire_type, &mut value, buf, ctx)?;
+                    self.value = Bytes::from(value);
+                    Ok(())
+                }
+                2 => encoding::message::merge(
+                    wire_type,
+                    self.deadline.get_or_insert_with(Deadline::default),
+                    buf,
+                    ctx,
+                ),
+                3 => encoding::int32::merge(wire_type, &mut self.priority, buf, ctx),
+                4 => encoding::message::merge(
+                    wire_type,
+                    self.topic.get_or_insert_with(Topic::default),
+                    buf,
+                    ctx,
+                ),
+                _ => encoding::skip_field(wire_type, tag, buf, ctx),
+            }
+        }
+
+        fn encoded_len(&self) -> usize {
+            let value = if self.value.is_empty() {
+                0
+            } else {
+                encoding::key_len(1)
+                    + encoding::encoded_len_varint(self.value.len() as u64)
+                    + self.value.len()
+            };
+            let priority = if self.priority != Priority::default() as i32 {
+                encoding::int32::encoded_len(3, &self.priority)
+            } else {
+                0
+            };
+            value
+                + self
+                    .deadline
+                    .as_ref()
+                    .map_or(0, |deadline| encoding::message::encoded_len(2, deadline))
+                + priority
+                + self
+                    .topic
+                    .as_ref()
+                    .map_or(0, |topic| encoding::message::encoded_len(4, topic))
+        }
+
+        fn clear(&mut self) {
+            *self = Content::default();
+        }
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Deadline {
+        #[prost(uint64, tag = "1")]
+        pub secs: u64,
+        #[prost(uint32, tag = "2")]
+        pub nanos: u32,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    pub enum Priority {
+        Normal = 0,
+        Bulk = 1,
+        High = 2,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Topic {
+        #[prost(string, tag = "1")]
+        pub name: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct State {
+        #[prost(oneof = "state::Phase", tags = "1, 2, 3")]
+        pub phase: Option<state::Phase>,
+    }
+
+    pub mod state {
+        #[derive(Clone, PartialEq, prost::Oneof)]
+        pub enum Phase {
+            #[prost(message, tag = "1")]
+            B(super::PhaseB),
+            #[prost(message, tag = "2")]
+            C(super::PhaseC),
+            #[prost(message, tag = "3")]
+            D(super::PhaseD),
+        }
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct PhaseB {
+        #[prost(uint32, tag = "1")]
+        pub round: u32,
+        #[prost(uint32, tag = "2")]
+        pub age: u32,
+        #[prost(message, repeated, tag = "3")]
+        pub player_ages: Vec<PlayerAge>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct PlayerAge {
+        #[prost(bytes, tag = "1")]
+        pub player: Vec<u8>,
+        #[prost(uint32, tag = "2")]
+        pub age: u32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct PhaseC {
+        #[prost(uint32, tag = "1")]
+        pub rounds_in_state_b: u32,
+        #[prost(uint32, tag = "2")]
+        pub round: u32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct PhaseD {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Subscriptions {
+        #[prost(bytes, tag = "1")]
+        pub player: Vec<u8>,
+        #[prost(string, repeated, tag = "2")]
+        pub topics: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Versions {
+        #[prost(uint32, tag = "1")]
+        pub min: u32,
+        #[prost(uint32, tag = "2")]
+        pub max: u32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct KeyRotation {
+        #[prost(bytes, tag = "1")]
+        pub public_id: Vec<u8>,
+        #[prost(bytes, tag = "2")]
+        pub sig: Vec<u8>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unwrap::unwrap;
+
+    // The layout `proto::Content` had while prost derived it.
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct DerivedContent {
+        #[prost(bytes, tag = "1")]
+        value: Vec<u8>,
+        #[prost(message, optional, tag = "2")]
+        deadline: Option<proto::Deadline>,
+        #[prost(enumeration = "proto::Priority", tag = "3")]
+        priority: i32,
+        #[prost(message, optional, tag = "4")]
+        topic: Option<proto::Topic>,
+    }
+
+    #[test]
+    fn content_encoding_is_unchanged() {
+        let contents = [
+            Content::new(vec![]),
+            Content::new(vec![1, 2, 3])
+                .with_ttl(Duration::from_secs(60))
+                .with_priority(Priority::High)
+                .with_topic(Topic("topic".to_string())),
+        ];
+        for content in &contents {
+            let proto = unwrap!(
+                rumor_to_proto(&Rumor {
+                    content: content.clone(),
+                    callee: ObliviousPlayer { id: Id([1; 32]) },
+                    state: State::new(),
+                    caller: InformedPlayer { id: Id([2; 32]) },
+                })
+                .content
+            );
+            let derived = DerivedContent {
+                value: proto.value.to_vec(),
+                deadline: proto.deadline.clone(),
+                priority: proto.priority,
+                topic: proto.topic.clone(),
+            };
+            let encoded = encode(&proto);
+            assert_eq!(encoded, encode(&derived));
+            assert_eq!(prost::Message::encoded_len(&proto), encoded.len());
+            let decoded: proto::Content = unwrap!(prost::Message::decode(&encoded[..]));
+            assert_eq!(decoded, proto);
+        }
+    }
+
+    fn encode<M: prost::Message>(message: &M) -> Vec<u8> {
+        let mut bytes = vec![];
+        unwrap!(prost::Message::encode(message, &mut bytes));
+        bytes
+    }
+
+    #[test]
+    fn out_of_range_deadlines() {
+        let rumor = Rumor {
+            content: Content::new(vec![1]),
+            callee: ObliviousPlayer { id: Id([1; 32]) },
+            state: State::new(),
+            caller: InformedPlayer { id: Id([2; 32]) },
+        };
+        let with_deadline = |secs, nanos| {
+            let mut proto = rumor_to_proto(&rumor);
+            unwrap!(proto.content.as_mut()).deadline = Some(proto::Deadline { secs, nanos });
+            rumor_from_proto(proto)
+        };
+        assert!(with_deadline(60, 999_999_999).is_ok());
+        for (secs, nanos) in [(u64::MAX, 1_000_000_000), (u64::MAX, 0), (0, u32::MAX)] {
+            match with_deadline(secs, nanos) {
+                Err(Error::Serialisation(_)) => (),
+                result => panic!("Unexpected result {:?}", result),
+            }
+        }
+    }
+}
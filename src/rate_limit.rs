@@ -0,0 +1,263 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use crate::id::Id;
+use crate::identity::PublicId;
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// What happens to the transmissions a player sends beyond its rate limits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// They are dropped.
+    Drop,
+    /// They are held back until the player is within its limits again, up to
+    /// `RateLimitConfig::max_delayed` of them, beyond which they are dropped.
+    Delay,
+}
+
+/// Limits on the transmissions handled from each player, enforced with token buckets.
+#[derive(Clone, Debug)]
+pub struct RateLimitConfig {
+    /// The transmissions handled per second from a player, or `None` for no limit.
+    pub messages_per_sec: Option<u32>,
+    /// The bytes of transmissions handled per second from a player, or `None` for no limit.
+    pub bytes_per_sec: Option<u64>,
+    /// How long a player may send at its full rate after being silent, which sets the size of
+    /// its buckets.
+    pub burst: Duration,
+    /// What happens to the transmissions beyond the limits.
+    pub overflow: Overflow,
+    /// The most transmissions held back per player when delaying.
+    pub max_delayed: usize,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            messages_per_sec: None,
+            bytes_per_sec: None,
+            burst: Duration::from_secs(1),
+            overflow: Overflow::Drop,
+            max_delayed: 64,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    // The limits on the transmissions and on the bytes per second, if any.
+    fn rates(&self) -> [Option<f64>; 2] {
+        [
+            self.messages_per_sec.map(f64::from),
+            self.bytes_per_sec.map(|rate| rate as f64),
+        ]
+    }
+
+    fn capacity(&self, rate: f64) -> f64 {
+        (rate * self.burst.as_secs_f64()).max(1.0)
+    }
+}
+
+// Tokens are added at `rate` per second, up to the capacity.
+#[derive(Clone, Copy, Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn refill(&mut self, rate: f64, capacity: f64, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(capacity);
+        self.updated = now;
+    }
+}
+
+// The buckets of a player, for transmissions and for bytes, and the transmissions held back.
+struct Sender<P> {
+    buckets: [TokenBucket; 2],
+    delayed: VecDeque<(P, Vec<u8>)>,
+}
+
+impl<P> Sender<P> {
+    fn new(config: &RateLimitConfig, now: Instant) -> Self {
+        let bucket = |rate: Option<f64>| TokenBucket {
+            tokens: rate.map_or(0.0, |rate| config.capacity(rate)),
+            updated: now,
+        };
+        let [messages, bytes] = config.rates();
+        Self {
+            buckets: [bucket(messages), bucket(bytes)],
+            delayed: VecDeque::new(),
+        }
+    }
+
+    // Takes the tokens for a transmission of `len` bytes, if each limited bucket holds them.
+    // One costing more than a bucket can hold goes through once the bucket is full, leaving
+    // it in debt, so that it isn't held back forever.
+    fn take(&mut self, config: &RateLimitConfig, len: usize, now: Instant) -> bool {
+        let costs = [1.0, len as f64];
+        let rates = config.rates();
+        for ((bucket, rate), cost) in self.buckets.iter_mut().zip(&rates).zip(&costs) {
+            if let Some(rate) = *rate {
+                let capacity = config.capacity(rate);
+                bucket.refill(rate, capacity, now);
+                if bucket.tokens < cost.min(capacity) {
+                    return false;
+                }
+            }
+        }
+        for ((bucket, rate), cost) in self.buckets.iter_mut().zip(&rates).zip(&costs) {
+            if rate.is_some() {
+                bucket.tokens -= cost;
+            }
+        }
+        true
+    }
+
+    // Whether the player is within its limits with nothing held back, so needn't be remembered.
+    fn is_idle(&mut self, config: &RateLimitConfig, now: Instant) -> bool {
+        let rates = config.rates();
+        self.delayed.is_empty()
+            && self.buckets.iter_mut().zip(&rates).all(|(bucket, rate)| {
+                rate.is_none_or(|rate| {
+                    let capacity = config.capacity(rate);
+                    bucket.refill(rate, capacity, now);
+                    bucket.tokens >= capacity
+                })
+            })
+    }
+}
+
+/// Enforces the rate limits on the transmissions received from each player.
+pub(crate) struct RateLimiter<P> {
+    config: RateLimitConfig,
+    senders: BTreeMap<Id, Sender<P>>,
+    dropped: u64,
+}
+
+impl<P: PublicId> Default for RateLimiter<P> {
+    fn default() -> Self {
+        Self::new(RateLimitConfig::default())
+    }
+}
+
+impl<P: PublicId> RateLimiter<P> {
+    pub(crate) fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            senders: BTreeMap::new(),
+            dropped: 0,
+        }
+    }
+
+    /// The number of transmissions dropped so far.
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Returns the transmissions to handle now: those held back earlier which are now within
+    /// the limits, followed by those of `incoming` within them, in the order each player sent
+    /// them.  The others are held back or dropped.
+    pub(crate) fn admit(&mut self, incoming: Vec<(P, Vec<u8>)>) -> Vec<(P, Vec<u8>)> {
+        if self.config.messages_per_sec.is_none() && self.config.bytes_per_sec.is_none() {
+            return incoming;
+        }
+        let now = Instant::now();
+        let config = &self.config;
+        let mut admitted = vec![];
+        for sender in self.senders.values_mut() {
+            while let Some(len) = sender.delayed.front().map(|(_, frame)| frame.len()) {
+                if !sender.take(config, len, now) {
+                    break;
+                }
+                admitted.extend(sender.delayed.pop_front());
+            }
+        }
+        for (public_id, frame) in incoming {
+            let sender = self
+                .senders
+                .entry(public_id.id())
+                .or_insert_with(|| Sender::new(config, now));
+            if sender.delayed.is_empty() && sender.take(config, frame.len(), now) {
+                admitted.push((public_id, frame));
+            } else if config.overflow == Overflow::Delay
+                && sender.delayed.len() < config.max_delayed
+            {
+                sender.delayed.push_back((public_id, frame));
+            } else {
+                debug!(
+                    "Dropped a transmission from {:?} over its rate limit",
+                    public_id
+                );
+                self.dropped += 1;
+            }
+        }
+        self.senders
+            .retain(|_, sender| !sender.is_idle(config, now));
+        admitted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::tests::TestId;
+    use std::thread;
+
+    fn frames(count: usize, len: usize) -> Vec<(TestId, Vec<u8>)> {
+        (0..count)
+            .map(|i| (TestId(1), vec![i as u8; len]))
+            .collect()
+    }
+
+    #[test]
+    fn excess_is_dropped() {
+        let mut limiter = RateLimiter::new(RateLimitConfig {
+            messages_per_sec: Some(2),
+            ..RateLimitConfig::default()
+        });
+        assert_eq!(limiter.admit(frames(5, 10)).len(), 2);
+        assert_eq!(limiter.dropped(), 3);
+        // Other players have their own buckets.
+        assert_eq!(limiter.admit(vec![(TestId(2), vec![0; 10])]).len(), 1);
+
+        let mut limiter = RateLimiter::new(RateLimitConfig {
+            bytes_per_sec: Some(100),
+            ..RateLimitConfig::default()
+        });
+        // The bucket holds 100 bytes, but a bigger transmission isn't held back forever.
+        assert_eq!(limiter.admit(frames(1, 1000)).len(), 1);
+        assert!(limiter.admit(frames(1, 10)).is_empty());
+    }
+
+    #[test]
+    fn excess_is_delayed() {
+        let mut limiter = RateLimiter::new(RateLimitConfig {
+            messages_per_sec: Some(1000),
+            burst: Duration::from_millis(2),
+            overflow: Overflow::Delay,
+            max_delayed: 5,
+            ..RateLimitConfig::default()
+        });
+        let admitted = limiter.admit(frames(10, 10));
+        assert_eq!(admitted.len(), 2);
+        assert_eq!(limiter.dropped(), 3);
+
+        thread::sleep(Duration::from_millis(20));
+        let admitted: Vec<u8> = limiter
+            .admit(vec![])
+            .into_iter()
+            .map(|(_, frame)| frame[0])
+            .collect();
+        // The held back transmissions come in order, as far as the bucket allows.
+        assert_eq!(admitted, vec![2, 3]);
+        assert_eq!(limiter.senders[&TestId(1).id()].delayed.len(), 3);
+    }
+}
@@ -0,0 +1,79 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use crate::error::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// How far behind the highest sequence number received a transmission may be, to allow for
+// reordering by the transport.
+const WINDOW_SIZE: u64 = 64;
+
+/// The sequence numbers received from one player, within a window trailing the highest one.
+/// Every transmission carries one, so none is accepted twice, however long ago it was sent.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ReplayWindow {
+    highest: u64,
+    // Bit `n` is set if `highest - n` was received.
+    received: u64,
+}
+
+impl ReplayWindow {
+    /// Records `sequence` as received, unless it was received before or is too old to tell.
+    pub(crate) fn receive(&mut self, sequence: u64) -> Result<(), Error> {
+        if sequence > self.highest || self.received == 0 {
+            let shift = sequence.saturating_sub(self.highest);
+            self.received = if shift >= WINDOW_SIZE {
+                0
+            } else {
+                self.received << shift
+            };
+            self.received |= 1;
+            self.highest = sequence;
+            return Ok(());
+        }
+        let offset = self.highest - sequence;
+        if offset >= WINDOW_SIZE || self.received & (1 << offset) != 0 {
+            return Err(Error::Replay);
+        }
+        self.received |= 1 << offset;
+        Ok(())
+    }
+}
+
+/// The first sequence number used by a node.  Starting from the current time rather than zero
+/// keeps the numbers increasing across restarts, so players don't take us for a replay.
+pub(crate) fn first_sequence() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replays_are_rejected() {
+        let mut window = ReplayWindow::default();
+        assert!(window.receive(1000).is_ok());
+        assert!(window.receive(1000).is_err());
+        // Reordered but not yet received.
+        assert!(window.receive(990).is_ok());
+        assert!(window.receive(990).is_err());
+        assert!(window.receive(1001).is_ok());
+        // Too old to tell whether it was received.
+        match window.receive(1001 - WINDOW_SIZE) {
+            Err(Error::Replay) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert!(window.receive(2000).is_ok());
+        assert!(window.receive(1001).is_err());
+    }
+}
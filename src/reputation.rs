@@ -0,0 +1,251 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use crate::error::Error;
+use crate::id::Id;
+use std::cmp;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// The ways in which a player can misbehave.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Misbehaviour {
+    /// Signed a payload which can't be decrypted or decoded.
+    MalformedPayload,
+    /// Sent a transmission on behalf of another player: a gossip naming another caller, or
+    /// a bootstrap request or subscriptions naming another player.
+    CallerMismatch,
+    /// Sent rumors in a state the protocol can't lead to, such as state C for a rumor we
+    /// initiated too recently for anyone to have got it there.  A rumor we have never seen
+    /// may well come in state C, since players in state C keep pushing it to those they
+    /// believe oblivious, so it isn't counted.
+    ImplausibleState,
+}
+
+impl Misbehaviour {
+    /// The misbehaviour a failure to handle a transmission from a player is down to, if any.
+    /// Failures which may well be down to the network or to us, like replays, aren't counted.
+    /// Only errors from handling a transmission whose signature verified are to be classified:
+    /// until then, the sender is only who the transport claims it is, and anyone could get
+    /// another player quarantined.  Nor are those from sending our reply, which may be of the
+    /// same kinds.
+    pub(crate) fn of(error: &Error) -> Option<Misbehaviour> {
+        match *error {
+            Error::DecryptionFailure
+            | Error::Encoding(_)
+            | Error::Serialisation(_)
+            | Error::FrameTooLarge(_)
+            | Error::TooManyRumors(_)
+            | Error::ContentTooLarge(_) => Some(Misbehaviour::MalformedPayload),
+            Error::CallerMismatch => Some(Misbehaviour::CallerMismatch),
+            Error::ImplausibleState => Some(Misbehaviour::ImplausibleState),
+            _ => None,
+        }
+    }
+}
+
+/// Reported to the application when a player is quarantined, and when it is released.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuarantineEvent {
+    /// The player misbehaved too much, and is left out of the push rounds for a while.
+    Quarantined(Id),
+    /// The quarantine of the player is over, and it starts afresh.
+    Released(Id),
+}
+
+/// How players are scored on their misbehaviour.
+#[derive(Clone, Debug)]
+pub struct ReputationConfig {
+    /// The score of a player we have nothing against, which is also the most it can have.
+    pub max_score: u32,
+    /// The score lost for each signed payload which can't be decrypted or decoded.
+    pub malformed_payload_penalty: u32,
+    /// The score lost for each transmission sent on behalf of another player.
+    pub caller_mismatch_penalty: u32,
+    /// The score lost for each gossip holding rumors in a state the protocol can't lead to.
+    pub implausible_state_penalty: u32,
+    /// A player is given back one point of score every this often, up to `max_score`.
+    pub recovery_interval: Duration,
+    /// How long a player whose score dropped to zero is quarantined for.
+    pub quarantine_duration: Duration,
+}
+
+impl Default for ReputationConfig {
+    fn default() -> Self {
+        Self {
+            max_score: 100,
+            malformed_payload_penalty: 10,
+            caller_mismatch_penalty: 25,
+            implausible_state_penalty: 20,
+            recovery_interval: Duration::from_secs(1),
+            quarantine_duration: Duration::from_secs(300),
+        }
+    }
+}
+
+impl ReputationConfig {
+    fn penalty(&self, misbehaviour: Misbehaviour) -> u32 {
+        match misbehaviour {
+            Misbehaviour::MalformedPayload => self.malformed_payload_penalty,
+            Misbehaviour::CallerMismatch => self.caller_mismatch_penalty,
+            Misbehaviour::ImplausibleState => self.implausible_state_penalty,
+        }
+    }
+}
+
+// The score of a player which misbehaved, as of `updated`.
+#[derive(Clone, Copy, Debug)]
+struct Standing {
+    score: u32,
+    updated: Instant,
+    quarantined_until: Option<Instant>,
+}
+
+/// The scores of the players which misbehaved.  Players we have nothing against aren't tracked.
+#[derive(Debug, Default)]
+pub(crate) struct Reputation {
+    config: ReputationConfig,
+    standings: BTreeMap<Id, Standing>,
+}
+
+impl Reputation {
+    pub(crate) fn new(config: ReputationConfig) -> Self {
+        Self {
+            config,
+            standings: BTreeMap::new(),
+        }
+    }
+
+    /// The current score of a player.
+    pub(crate) fn score(&self, id: &Id) -> u32 {
+        self.standings
+            .get(id)
+            .map_or(self.config.max_score, |standing| {
+                self.recovered(standing, Instant::now())
+            })
+    }
+
+    /// Whether a player is in quarantine.
+    pub(crate) fn is_quarantined(&self, id: &Id) -> bool {
+        self.standings
+            .get(id)
+            .is_some_and(|standing| standing.quarantined_until.is_some())
+    }
+
+    /// Lowers the score of a player for `misbehaviour`, returning whether this got it
+    /// quarantined.  A player already in quarantine stays in it until it's over.
+    pub(crate) fn penalise(&mut self, id: Id, misbehaviour: Misbehaviour) -> bool {
+        let now = Instant::now();
+        let max_score = self.config.max_score;
+        let standing = *self.standings.entry(id).or_insert(Standing {
+            score: max_score,
+            updated: now,
+            quarantined_until: None,
+        });
+        if standing.quarantined_until.is_some() {
+            return false;
+        }
+        let score = self
+            .recovered(&standing, now)
+            .saturating_sub(self.config.penalty(misbehaviour));
+        let quarantined_until = if score == 0 {
+            Some(now + self.config.quarantine_duration)
+        } else {
+            None
+        };
+        let _ = self.standings.insert(
+            id,
+            Standing {
+                score,
+                updated: now,
+                quarantined_until,
+            },
+        );
+        quarantined_until.is_some()
+    }
+
+    /// Ends the quarantines which are over, returning the players released.  Those start
+    /// afresh, with the maximum score.
+    pub(crate) fn release_expired(&mut self) -> Vec<Id> {
+        let now = Instant::now();
+        let released: Vec<Id> = self
+            .standings
+            .iter()
+            .filter(|(_, standing)| standing.quarantined_until.is_some_and(|until| until <= now))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &released {
+            let _ = self.standings.remove(id);
+        }
+        released
+    }
+
+    /// Carries the standing of a player over to the new id it rotated its key to.
+    pub(crate) fn rotate(&mut self, old: Id, new: Id) {
+        if let Some(standing) = self.standings.remove(&old) {
+            let _ = self.standings.insert(new, standing);
+        }
+    }
+
+    // The score of `standing` with the points recovered since it was last updated.
+    fn recovered(&self, standing: &Standing, now: Instant) -> u32 {
+        if standing.quarantined_until.is_some() {
+            return standing.score;
+        }
+        let interval = cmp::max(self.config.recovery_interval.as_nanos(), 1);
+        let recovered = now.duration_since(standing.updated).as_nanos() / interval;
+        cmp::min(
+            u128::from(standing.score) + recovered,
+            u128::from(self.config.max_score),
+        ) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn misbehaving_players_are_quarantined() {
+        let mut reputation = Reputation::new(ReputationConfig {
+            recovery_interval: Duration::from_secs(3600),
+            quarantine_duration: Duration::from_millis(0),
+            ..ReputationConfig::default()
+        });
+        let id = Id([1; 32]);
+        assert_eq!(reputation.score(&id), 100);
+        for _ in 0..3 {
+            assert!(!reputation.penalise(id, Misbehaviour::CallerMismatch));
+        }
+        assert_eq!(reputation.score(&id), 25);
+        assert!(!reputation.penalise(id, Misbehaviour::MalformedPayload));
+        assert!(reputation.penalise(id, Misbehaviour::ImplausibleState));
+        assert_eq!(reputation.score(&id), 0);
+        // Already quarantined.
+        assert!(!reputation.penalise(id, Misbehaviour::ImplausibleState));
+
+        assert_eq!(reputation.release_expired(), vec![id]);
+        assert_eq!(reputation.score(&id), 100);
+        assert!(reputation.release_expired().is_empty());
+    }
+
+    #[test]
+    fn scores_recover() {
+        let mut reputation = Reputation::new(ReputationConfig {
+            recovery_interval: Duration::from_nanos(1),
+            ..ReputationConfig::default()
+        });
+        let id = Id([1; 32]);
+        for _ in 0..10 {
+            assert!(!reputation.penalise(id, Misbehaviour::CallerMismatch));
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(reputation.score(&id), 100);
+    }
+}
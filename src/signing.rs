@@ -0,0 +1,68 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// http://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use crate::error::Error;
+use crate::identity::{PublicId, SecretId};
+
+/// Signs the transmissions we send.
+pub trait Signer<S: SecretId>: Send {
+    /// Signs `data` on behalf of the owner of `secret_id`.
+    fn sign(&self, secret_id: &S, data: &[u8]) -> Result<Vec<u8>, Error>;
+}
+
+/// Verifies the signatures of the transmissions we receive.
+pub trait Verifier<P: PublicId>: Send {
+    /// Checks that `sig` was made over `data` by the owner of `public_id`.
+    fn verify(&self, public_id: &P, data: &[u8], sig: &[u8]) -> Result<(), Error>;
+
+    /// Checks a batch of signatures, returning the outcome for each in order.
+    fn verify_batch(&self, batch: &[(&P, &[u8], &[u8])]) -> Vec<Result<(), Error>> {
+        batch
+            .iter()
+            .map(|(public_id, data, sig)| self.verify(public_id, data, sig))
+            .collect()
+    }
+}
+
+/// Signs and verifies with the signature scheme of the identities.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NativeSigner;
+
+impl<S: SecretId> Signer<S> for NativeSigner {
+    fn sign(&self, secret_id: &S, data: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(secret_id.sign(data))
+    }
+}
+
+impl<P: PublicId> Verifier<P> for NativeSigner {
+    fn verify(&self, public_id: &P, data: &[u8], sig: &[u8]) -> Result<(), Error> {
+        public_id.verify(data, sig)
+    }
+
+    fn verify_batch(&self, batch: &[(&P, &[u8], &[u8])]) -> Vec<Result<(), Error>> {
+        P::verify_batch(batch)
+    }
+}
+
+/// Signs with a blank signature, and accepts any signature.  This provides no authenticity at
+/// all, and is only meant for benchmarks which want to leave out the cost of signatures.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NullSigner;
+
+impl<S: SecretId> Signer<S> for NullSigner {
+    fn sign(&self, _secret_id: &S, _data: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(vec![])
+    }
+}
+
+impl<P: PublicId> Verifier<P> for NullSigner {
+    fn verify(&self, _public_id: &P, _data: &[u8], _sig: &[u8]) -> Result<(), Error> {
+        Ok(())
+    }
+}
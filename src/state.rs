@@ -10,6 +10,62 @@
 use crate::id::Id;
 use std::collections::BTreeMap;
 
+/// How a player in state B updates its age for a rumor from the ages other players report.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AgeRule {
+    /// The median rule of the paper: our age grows if no fewer players report an age at least
+    /// as high as ours than report a lower one, and a single player in state C takes us there.
+    #[default]
+    Median,
+    /// The median rule, tolerating faulty players reporting arbitrary ages.  We only move to
+    /// state C once more than `max_faulty` players report being in it, and the median rule is
+    /// applied to the ages left once the `max_faulty` highest and lowest are discarded.  A
+    /// rumor first received from a player in state C starts in state B.
+    Byzantine {
+        /// The number of faulty players to tolerate.
+        max_faulty: usize,
+    },
+}
+
+// What the ages reported in a round lead to.
+enum AgeStep {
+    StateC,
+    Grow,
+    Stay,
+}
+
+impl AgeRule {
+    fn step(self, age: Age, age_max: Age, player_ages: &BTreeMap<Id, Age>) -> AgeStep {
+        let mut ages: Vec<Age> = player_ages.values().copied().collect();
+        let max_faulty = match self {
+            AgeRule::Median => 0,
+            AgeRule::Byzantine { max_faulty } => max_faulty,
+        };
+        if ages
+            .iter()
+            .filter(|player_age| **player_age >= age_max)
+            .count()
+            > max_faulty
+        {
+            return AgeStep::StateC;
+        }
+        if ages.len() <= max_faulty.saturating_mul(2) {
+            return AgeStep::Stay;
+        }
+        ages.sort();
+        let trusted = &ages[max_faulty..ages.len() - max_faulty];
+        let less = trusted
+            .iter()
+            .filter(|player_age| **player_age < age)
+            .count();
+        if trusted.len() - less > less {
+            AgeStep::Grow
+        } else {
+            AgeStep::Stay
+        }
+    }
+}
+
 /// This represents the state of a single rumor from this player's perspective.
 #[derive(Serialize, Debug, Deserialize, Clone, PartialEq)]
 pub enum State {
@@ -60,8 +116,15 @@ impl State {
 
     /// Construct a new `State` where we've received the rumor from a player.  If that player
     /// is in state B (`age < max_b_age`) we start in state B with `age` set to `1`.
-    /// If the player is in state C, we start in state C too.
-    pub fn new_from_player(player_age: Age, max_b_age: Age) -> Self {
+    /// If the player is in state C, we start in state C too, unless following the Byzantine
+    /// rule, in which case we start in state B with the player's age counting towards the
+    /// next round.
+    pub fn new_from_player(player_id: Id, player_age: Age, max_b_age: Age, rule: AgeRule) -> Self {
+        if let AgeRule::Byzantine { .. } = rule {
+            let mut state = State::new();
+            state.receive_rumor(player_id, player_age);
+            return state;
+        }
         if player_age < max_b_age {
             return State::B {
                 round: Round::default(),
@@ -89,12 +152,14 @@ impl State {
         }
     }
 
-    /// Increment `round` value, consuming `self` and returning the new state.
+    /// Increment `round` value, consuming `self` and returning the new state.  In state B, our
+    /// age is updated from the ages received as per `rule`.
     pub fn next_round(
         self,
         age_max: Age,
         max_c_rounds: Round,
         max_rounds: Round,
+        rule: AgeRule,
         //players_in_this_round: &BTreeSet<Id>,
     ) -> State {
         match self {
@@ -122,23 +187,17 @@ impl State {
                 // // }
 
                 // Apply the median rule, but if any player's age >= `age_max` (i.e. that player
-                // is in state C), transition to state C.
-                let mut less = 0;
-                let mut greater_or_equal = 0;
-                for player_age in player_ages.values() {
-                    if *player_age < age {
-                        less += 1;
-                    } else if *player_age >= age_max {
+                // is in state C), transition to state C.  The Byzantine rule requires more than
+                // the faulty players to be in state C, and disregards outlying ages.
+                match rule.step(age, age_max, &player_ages) {
+                    AgeStep::StateC => {
                         return State::C {
                             rounds_in_state_b: round,
                             round: Round::default(),
                         };
-                    } else {
-                        greater_or_equal += 1;
                     }
-                }
-                if greater_or_equal > less {
-                    age += Age::from(1);
+                    AgeStep::Grow => age += Age::from(1),
+                    AgeStep::Stay => (),
                 }
 
                 // If our age has reached `age_max`, transition to state C, otherwise remain
@@ -180,6 +239,19 @@ impl State {
         }
     }
 
+    /// Returns whether a player following the protocol can be in this state.  In state B, the
+    /// age starts at 1 and grows by at most 1 per round, and `Age::max()` stands for state C.
+    pub fn is_plausible(&self) -> bool {
+        match *self {
+            State::B { round, age, .. } => {
+                age >= Age::from(1)
+                    && age < Age::max()
+                    && age.value <= round.value.saturating_add(1)
+            }
+            State::C { .. } | State::D => true,
+        }
+    }
+
     /// We only need to push and pull this rumor if we're in states B or C, hence this returns
     /// `None` if we're in state D.  State C is indicated by returning a value > `age_max`.
     pub fn get_age(&self) -> Option<Age> {
@@ -191,9 +263,12 @@ impl State {
     }
 }
 
-#[derive(Copy, Clone, Serialize, Debug, Deserialize, PartialEq, PartialOrd)]
+/// The age of a rumor in state B.  Ages only grow up to `O(ln n)`, and `Age::max()` stands for
+/// state C, so the byte which goes on the wire is plenty.  Additions saturate at the maximum
+/// rather than overflowing.
+#[derive(Copy, Clone, Serialize, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Age {
-    value: u8,
+    pub(crate) value: u8,
 }
 
 impl Age {
@@ -204,21 +279,28 @@ impl Age {
         Self { value }
     }
     pub fn max() -> Self {
-        Self {
-            value: u8::max_value(),
-        }
+        Self { value: u8::MAX }
+    }
+}
+
+impl std::ops::Add for Age {
+    type Output = Age;
+    fn add(self, rhs: Self) -> Age {
+        Age::from(self.value.saturating_add(rhs.value))
     }
 }
 
 impl std::ops::AddAssign for Age {
     fn add_assign(&mut self, rhs: Self) {
-        self.value += rhs.value;
+        *self = *self + rhs;
     }
 }
 
-#[derive(Default, Copy, Clone, Serialize, Debug, Deserialize, PartialEq, PartialOrd)]
+/// A number of rounds of a rumor, bounded by `O(ln n)` too.  Additions saturate at the maximum,
+/// which no bound they are compared to exceeds, so that a saturated sum still reaches it.
+#[derive(Default, Copy, Clone, Serialize, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Round {
-    value: u8,
+    pub(crate) value: u8,
 }
 
 impl Round {
@@ -230,12 +312,176 @@ impl Round {
 impl std::ops::Add for Round {
     type Output = Round;
     fn add(self, rhs: Self) -> Round {
-        Round::from(self.value + rhs.value)
+        Round::from(self.value.saturating_add(rhs.value))
     }
 }
 
 impl std::ops::AddAssign for Round {
     fn add_assign(&mut self, rhs: Self) {
-        self.value += rhs.value;
+        *self = *self + rhs;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        Receive(u8, Age),
+        NextRound,
+    }
+
+    #[derive(Clone, Debug)]
+    struct Params {
+        age_max: Age,
+        max_c_rounds: Round,
+        max_rounds: Round,
+        rule: AgeRule,
+    }
+
+    fn params() -> impl Strategy<Value = Params> {
+        let rule = prop_oneof![
+            Just(AgeRule::Median),
+            (0..4usize).prop_map(|max_faulty| AgeRule::Byzantine { max_faulty }),
+        ];
+        (1..10u8, 1..10u8, 1..20u8, rule).prop_map(|(age_max, max_c_rounds, max_rounds, rule)| {
+            Params {
+                age_max: Age::from(age_max),
+                max_c_rounds: Round::from(max_c_rounds),
+                max_rounds: Round::from(max_rounds),
+                rule,
+            }
+        })
+    }
+
+    // Ages from the whole range, as faulty players may send any.
+    fn ops() -> impl Strategy<Value = Vec<Op>> {
+        let op = prop_oneof![
+            (0..8u8, any::<u8>()).prop_map(|(player, age)| Op::Receive(player, Age::from(age))),
+            Just(Op::NextRound),
+        ];
+        vec(op, 0..64)
+    }
+
+    // Either we initiated the rumor, or we received it from a player with some age.
+    fn initial(params: &Params) -> impl Strategy<Value = State> {
+        let (age_max, rule) = (params.age_max, params.rule);
+        prop_oneof![
+            Just(State::new()),
+            (0..8u8, any::<u8>()).prop_map(move |(player, age)| {
+                State::new_from_player(Id([player; 32]), Age::from(age), age_max, rule)
+            }),
+        ]
+    }
+
+    fn cases() -> impl Strategy<Value = (Params, State, Vec<Op>)> {
+        params().prop_flat_map(|params| {
+            let initial = initial(&params);
+            (Just(params), initial, ops())
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn transitions((params, mut state, ops) in cases()) {
+            let mut rounds = 0;
+            for op in ops {
+                let before = state.clone();
+                match op {
+                    Op::Receive(player, age) => {
+                        state.receive_rumor(Id([player; 32]), age);
+                        // Receiving a rumor doesn't change our age or phase.
+                        prop_assert_eq!(state.get_age(), before.get_age());
+                        continue;
+                    }
+                    Op::NextRound => {
+                        state = state.next_round(
+                            params.age_max,
+                            params.max_c_rounds,
+                            params.max_rounds,
+                            params.rule,
+                        );
+                        rounds += 1;
+                    }
+                }
+
+                match (&before, &state) {
+                    // D is absorbing.
+                    (State::D, _) => prop_assert_eq!(&state, &State::D),
+                    // C never returns to B.
+                    (State::C { .. }, State::B { .. }) => prop_assert!(false, "{:?}", state),
+                    // The age grows by at most one per round in state B.
+                    (State::B { age: old, .. }, State::B { age, .. }) => {
+                        prop_assert!(*age <= *old + Age::from(1));
+                    }
+                    _ => (),
+                }
+                // The age never decreases, until the rumor is done with.
+                if let (Some(old), Some(age)) = (before.get_age(), state.get_age()) {
+                    prop_assert!(old <= age);
+                }
+                match state {
+                    State::B { age, .. } => {
+                        prop_assert!(age < params.age_max);
+                        prop_assert_eq!(state.get_age(), Some(age));
+                        prop_assert!(state.is_plausible());
+                    }
+                    State::C { .. } => prop_assert_eq!(state.get_age(), Some(Age::max())),
+                    State::D => prop_assert_eq!(state.get_age(), None),
+                }
+                // The rumor terminates within `max_rounds`.
+                if rounds >= params.max_rounds.value {
+                    prop_assert_eq!(&state, &State::D);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn extreme_values() {
+        assert_eq!(Age::max() + Age::from(1), Age::max());
+        let mut round = Round::from(u8::MAX);
+        round += Round::from(1);
+        assert_eq!(round, Round::from(u8::MAX));
+        assert!(Age::from(2) > Age::from(1));
+
+        // A saturated round still reaches the bound, ending the rumor.
+        let state = State::C {
+            rounds_in_state_b: Round::from(200),
+            round: Round::from(100),
+        };
+        let max = Round::from(u8::MAX);
+        assert_eq!(
+            state.next_round(Age::max(), max, max, AgeRule::Median),
+            State::D
+        );
+
+        let state = State::B {
+            round: Round::from(u8::MAX),
+            age: Age::from(1),
+            player_ages: BTreeMap::new(),
+        };
+        assert_eq!(
+            state.next_round(Age::max(), max, max, AgeRule::Median),
+            State::D
+        );
+
+        // Any number of faulty players to tolerate, and any ages reported, are handled.
+        let mut state = State::new();
+        state.receive_rumor(Id([1; 32]), Age::max());
+        state.receive_rumor(Id([2; 32]), Age::from(0));
+        let rule = AgeRule::Byzantine {
+            max_faulty: usize::MAX,
+        };
+        assert_eq!(
+            state.next_round(Age::from(0), max, max, rule),
+            State::C {
+                rounds_in_state_b: Round::from(1),
+                round: Round::default(),
+            }
+        );
     }
 }
@@ -7,76 +7,946 @@
 // specific language governing permissions and limitations relating to use of the SAFE Network
 // Software.
 
+//! The wire format of the messages exchanged between players.
+//!
+//! Every transmission is framed as follows:
+//!
+//! | bytes  | content                                              |
+//! |--------|------------------------------------------------------|
+//! | 0..4   | the magic bytes `MAGIC`                              |
+//! | 4..6   | the protocol version, as a big-endian `u16`          |
+//! | 6      | the message type (see `Kind`)                        |
+//! | 7      | the codec of the body and payload                    |
+//! | 8      | the compression of the payload                       |
+//! | 9      | the encryption of the payload                        |
+//! | 10..18 | the sequence number, a big-endian `u64`              |
+//! | 18..   | the body: group, payload and signature               |
+//!
+//! The sequence number increases with each frame sent by a node to a given player, and the
+//! frame is signed together with the id of its recipient.  This lets the recipient reject
+//! frames which were captured and sent again, to it or to another player.
+//!
+//! The payload is encoded, then compressed, then encrypted, and the signature covers every byte
+//! of the header as well as the group and the payload as sent.
+//!
+//! Compatibility policy: a node understands every version from `MIN_PROTOCOL_VERSION` up to
+//! `PROTOCOL_VERSION`, and never changes the meaning of a message type within a version.  Two
+//! nodes exchange `Hello` messages advertising the versions each supports before anything else,
+//! after which the highest version supported by both is used.  Until then a node sends with its
+//! newest version.  This allows a cluster to be upgraded one node at a time.
+
+use crate::codec::Codec;
+use crate::compression::{Compression, CompressionMetrics};
+use crate::config::{Config, Limits};
+use crate::encryption::{Encryption, SessionKey};
 use crate::error::Error;
-use crate::gossip::Gossip;
-use bincode::{deserialize, serialize};
-use ed25519_dalek::{Keypair, PublicKey, Signature};
-#[cfg(not(test))]
-use sha3::Sha3_512;
+use crate::gossip::{Gossip, Subscriptions};
+use crate::id::{GroupId, Id};
+use crate::identity::{PublicId, SecretId};
+use crate::signing::{Signer, Verifier};
+use bincode::serialize;
+use std::cmp;
+
+/// The bytes every transmission starts with.
+pub const MAGIC: [u8; 4] = *b"SGSP";
+/// The newest version of the wire protocol this node understands.
+pub const PROTOCOL_VERSION: u16 = 1;
+/// The oldest version of the wire protocol this node understands.
+pub const MIN_PROTOCOL_VERSION: u16 = 1;
+
+// The length of the frame header.
+const HEADER_LEN: usize = 18;
+
+/// Transmission via direct connection, wrapper of gossip.
+#[derive(Debug)]
+pub struct Transmission {
+    version: u16,
+    kind: Kind,
+    codec: Codec,
+    compression: Compression,
+    encryption: Encryption,
+    sequence: u64,
+    body: Body,
+    // The limits the payload is decoded within.
+    limits: Limits,
+    // The message, if the payload was decoded before the signature was verified.
+    message: Option<Message>,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
-pub enum Transmission {
+pub(crate) struct Body {
+    /// The group of the `Gossiping` instance this is sent from and to.
+    pub(crate) group: GroupId,
+    /// The encoded message, compressed and encrypted if so indicated in the header.
+    pub(crate) payload: Vec<u8>,
+    /// Signs the header and group as well as the payload, so that none can be altered.
+    #[serde(with = "serde_bytes")]
+    pub(crate) sig: Vec<u8>,
+}
+
+/// The message type of a transmission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Kind {
     /// Sent from Node A to Node B to push a message and its counter.
-    Push { payload: Vec<u8>, sig: Signature },
+    Push = 0,
     /// NOTE: Called Pull in the paper.
     /// Sent from Node B to Node A as a reaction to receiving a push message from A.
-    Response { payload: Vec<u8>, sig: Signature },
+    Response = 1,
+    /// Sent from a joining Node to an existing Node, to ask for recent rumors.
+    BootstrapRequest = 2,
+    /// Sent from an existing Node to a joining Node as a reaction to a bootstrap request.
+    Bootstrap = 3,
+    /// Sent from a Node to the other Nodes whenever the topics it is subscribed to change.
+    Subscriptions = 4,
+    /// Sent from a Node to another to negotiate the protocol version to use between them.
+    Hello = 5,
+    /// Sent from a Node to the other Nodes, signed with its old key, when it changes its key.
+    KeyRotation = 6,
+}
+
+impl Kind {
+    fn from_byte(byte: u8) -> Result<Kind, Error> {
+        Ok(match byte {
+            0 => Kind::Push,
+            1 => Kind::Response,
+            2 => Kind::BootstrapRequest,
+            3 => Kind::Bootstrap,
+            4 => Kind::Subscriptions,
+            5 => Kind::Hello,
+            6 => Kind::KeyRotation,
+            _ => return Err(Error::UnknownMessageType(byte)),
+        })
+    }
+}
+
+/// The range of protocol versions a node understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Versions {
+    pub min: u16,
+    pub max: u16,
+}
+
+impl Versions {
+    /// The versions this node understands.
+    pub fn ours() -> Self {
+        Versions {
+            min: MIN_PROTOCOL_VERSION,
+            max: PROTOCOL_VERSION,
+        }
+    }
+
+    /// Returns the highest version understood by both us and a node understanding `self`.
+    pub fn negotiate(&self) -> Result<u16, Error> {
+        let version = cmp::min(self.max, PROTOCOL_VERSION);
+        if version < cmp::max(self.min, MIN_PROTOCOL_VERSION) {
+            return Err(Error::IncompatibleVersion(self.max));
+        }
+        Ok(version)
+    }
+}
+
+/// The new identity of the sender, which the transmission is signed with the old identity of.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRotation {
+    /// The encoding of the new public identity, as given by `PublicId::to_bytes`.
+    #[serde(with = "serde_bytes")]
+    pub public_id: Vec<u8>,
+    /// The signature by the new identity over the bytes given by `KeyRotation::proof`, which
+    /// proves the sender holds the new key.
+    #[serde(with = "serde_bytes")]
+    pub sig: Vec<u8>,
+}
+
+impl KeyRotation {
+    /// The bytes the new identity signs, binding it to the rotation of `old` to `new`.
+    pub fn proof(old: &Id, new: &Id) -> Result<Vec<u8>, Error> {
+        Ok(serialize(&(old, new))?)
+    }
+}
+
+/// Who a transmission is sent to, and how.
+pub struct Envelope<'a> {
+    /// The id of the player the transmission is sent to.
+    pub recipient: Id,
+    /// The protocol version settled with the recipient.
+    pub version: u16,
+    /// The sequence number of the transmission.
+    pub sequence: u64,
+    /// The key shared with the recipient, required if encryption is configured.
+    pub session: Option<&'a SessionKey>,
+}
+
+/// The deserialised payload of a `Transmission`.
+#[derive(Debug)]
+pub enum Message {
+    Push(Gossip),
+    Response(Gossip),
+    /// Holds the id of the requesting player.
+    BootstrapRequest(Id),
+    Bootstrap(Gossip),
+    Subscriptions(Subscriptions),
+    Hello(Versions),
+    KeyRotation(KeyRotation),
 }
 
-/// Transmission via direct connection, wrapper of gossip.
-#[cfg(not(test))]
 impl Transmission {
-    pub fn get_value(&mut self) -> Result<(Gossip, bool), Error> {
-        match self {
-            Self::Push { payload, .. } => Ok((deserialize(payload)?, true)),
-            Self::Response { payload, .. } => Ok((deserialize(payload)?, false)),
+    pub fn group(&self) -> &GroupId {
+        &self.body.group
+    }
+
+    /// The sequence number given by the sender.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// How the payload was encrypted by the sender.
+    pub fn encryption(&self) -> Encryption {
+        self.encryption
+    }
+
+    /// Decodes the message, decrypting it first with `session`, the key shared with the sender,
+    /// if the payload is encrypted.  Gossips are checked against the limits the transmission was
+    /// deserialised with.
+    pub fn get_value(&mut self, session: Option<&SessionKey>) -> Result<Message, Error> {
+        if let Some(message) = self.message.take() {
+            return Ok(message);
+        }
+        if self.encryption == Encryption::None {
+            return self.decode(&self.body.payload);
         }
+        let session = session.ok_or(Error::DecryptionFailure)?;
+        let associated_data = associated_data(self.version, self.kind, &self.body.group)?;
+        self.decode(&session.open(&self.body.payload, &associated_data)?)
     }
 
-    pub fn serialise(gossip: &Gossip, is_push: bool, keys: &Keypair) -> Result<Vec<u8>, Error> {
-        let payload = serialize(gossip)?;
-        let sig: Signature = keys.sign::<Sha3_512>(&payload);
-        let transmission = if is_push {
-            Transmission::Push { payload, sig }
-        } else {
-            Transmission::Response { payload, sig }
+    // Decodes the decrypted `payload`, checking it against the limits.
+    fn decode(&self, payload: &[u8]) -> Result<Message, Error> {
+        let payload = &self
+            .compression
+            .decompress(payload, self.limits.max_frame_size)?;
+        let codec = self.codec;
+        let gossip = |payload| check_limits(codec.decode(payload)?, &self.limits);
+        Ok(match self.kind {
+            Kind::Push => Message::Push(gossip(payload)?),
+            Kind::Response => Message::Response(gossip(payload)?),
+            Kind::BootstrapRequest => Message::BootstrapRequest(codec.decode(payload)?),
+            Kind::Bootstrap => Message::Bootstrap(gossip(payload)?),
+            Kind::Subscriptions => Message::Subscriptions(codec.decode(payload)?),
+            Kind::Hello => Message::Hello(codec.decode(payload)?),
+            Kind::KeyRotation => Message::KeyRotation(codec.decode(payload)?),
+        })
+    }
+
+    /// Serialises `message` into a frame addressed as per `envelope`, encoded with the codec of
+    /// `config`.
+    /// If compression is configured, the payload is compressed unless it's below the threshold,
+    /// or compression would not make it any smaller.
+    /// If encryption is configured, the payload is encrypted with the session key of the
+    /// envelope.  The frame is signed by `signer` on behalf of the owner of `secret_id`.
+    pub fn serialise<S: SecretId>(
+        group: &GroupId,
+        message: &Message,
+        envelope: &Envelope,
+        config: &Config,
+        secret_id: &S,
+        signer: &dyn Signer<S>,
+        metrics: &mut CompressionMetrics,
+    ) -> Result<Vec<u8>, Error> {
+        let version = envelope.version;
+        let codec = config.codec;
+        let encryption = config.encryption;
+        let (kind, payload) = match message {
+            Message::Push(gossip) => (Kind::Push, codec.encode(gossip)?),
+            Message::Response(gossip) => (Kind::Response, codec.encode(gossip)?),
+            Message::BootstrapRequest(id) => (Kind::BootstrapRequest, codec.encode(id)?),
+            Message::Bootstrap(gossip) => (Kind::Bootstrap, codec.encode(gossip)?),
+            Message::Subscriptions(subscriptions) => {
+                (Kind::Subscriptions, codec.encode(subscriptions)?)
+            }
+            Message::Hello(versions) => (Kind::Hello, codec.encode(versions)?),
+            Message::KeyRotation(rotation) => (Kind::KeyRotation, codec.encode(rotation)?),
         };
-        Ok(serialize(&transmission)?)
+        let (compression, mut payload) = compress(payload, config, metrics)?;
+        if encryption != Encryption::None {
+            let session = envelope.session.ok_or(Error::KeyAgreementFailure)?;
+            payload = session.seal(&payload, &associated_data(version, kind, group)?)?;
+        }
+        let header = Header {
+            version,
+            kind,
+            codec,
+            compression,
+            encryption,
+            sequence: envelope.sequence,
+        };
+        let signed = signed_bytes(&header, group, &envelope.recipient, &payload)?;
+        let sig = signer.sign(secret_id, &signed)?;
+        let body = Body {
+            group: group.clone(),
+            payload,
+            sig,
+        };
+        let mut frame = MAGIC.to_vec();
+        frame.extend_from_slice(&version.to_be_bytes());
+        frame.push(kind as u8);
+        frame.push(codec.id());
+        frame.push(compression.id());
+        frame.push(encryption.id());
+        frame.extend_from_slice(&envelope.sequence.to_be_bytes());
+        frame.extend_from_slice(&codec.encode(&body)?);
+        Ok(frame)
     }
 
-    pub fn deserialise(payload: &[u8], key: &PublicKey) -> Result<Transmission, Error> {
-        let mut transmission: Transmission = deserialize(payload)?;
-        transmission.verify_sig(key)?;
-        Ok(transmission)
+    /// Parses the frame header, and the body as per the codec in it.
+    fn parse(frame: &[u8]) -> Result<Transmission, Error> {
+        if frame.len() < HEADER_LEN || frame[..4] != MAGIC {
+            return Err(Error::InvalidFrame);
+        }
+        let version = u16::from_be_bytes([frame[4], frame[5]]);
+        if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version) {
+            return Err(Error::IncompatibleVersion(version));
+        }
+        let kind = Kind::from_byte(frame[6])?;
+        let codec = Codec::from_id(frame[7])?;
+        let mut sequence = [0; 8];
+        sequence.copy_from_slice(&frame[10..HEADER_LEN]);
+        Ok(Transmission {
+            version,
+            kind,
+            codec,
+            compression: Compression::from_id(frame[8])?,
+            encryption: Encryption::from_id(frame[9])?,
+            sequence: u64::from_be_bytes(sequence),
+            body: codec.decode(&frame[HEADER_LEN..])?,
+            limits: Limits::default(),
+            message: None,
+        })
     }
+}
 
-    fn verify_sig(&mut self, key: &PublicKey) -> Result<(), Error> {
-        let (payload, sig) = match self {
-            Transmission::Push { payload, sig } => (payload, sig),
-            Transmission::Response { payload, sig } => (payload, sig),
-        };
-        if key.verify::<Sha3_512>(&payload, &sig).is_ok() {
-            Ok(())
-        } else {
-            Err(Error::SigFailure)
+// Rejects a gossip holding more or bigger rumors than `limits` allow.
+fn check_limits(gossip: Gossip, limits: &Limits) -> Result<Gossip, Error> {
+    if gossip.rumors.len() > limits.max_rumors_per_gossip {
+        return Err(Error::TooManyRumors(gossip.rumors.len()));
+    }
+    for rumor in &gossip.rumors {
+        if rumor.content.value.len() > limits.max_content_size {
+            return Err(Error::ContentTooLarge(rumor.content.value.len()));
         }
     }
+    Ok(gossip)
+}
+
+fn compress(
+    payload: Vec<u8>,
+    config: &Config,
+    metrics: &mut CompressionMetrics,
+) -> Result<(Compression, Vec<u8>), Error> {
+    if config.compression == Compression::None {
+        return Ok((Compression::None, payload));
+    }
+    if payload.len() < config.compression_threshold {
+        metrics.record(payload.len(), None);
+        return Ok((Compression::None, payload));
+    }
+    let compressed = config.compression.compress(payload.clone())?;
+    if compressed.len() >= payload.len() {
+        metrics.record(payload.len(), None);
+        return Ok((Compression::None, payload));
+    }
+    metrics.record(payload.len(), Some(compressed.len()));
+    Ok((config.compression, compressed))
+}
+
+// Binds an encrypted payload to the header it was sent with.
+fn associated_data(version: u16, kind: Kind, group: &GroupId) -> Result<Vec<u8>, Error> {
+    Ok(serialize(&(version, kind, group))?)
+}
+
+// The fields of a frame header, as signed.
+struct Header {
+    version: u16,
+    kind: Kind,
+    codec: Codec,
+    compression: Compression,
+    encryption: Encryption,
+    sequence: u64,
+}
+
+fn signed_bytes(
+    header: &Header,
+    group: &GroupId,
+    recipient: &Id,
+    payload: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let ids = (
+        header.codec.id(),
+        header.compression.id(),
+        header.encryption.id(),
+    );
+    let (version, kind, sequence) = (header.version, header.kind, header.sequence);
+    Ok(serialize(&(
+        version, kind, ids, group, recipient, sequence, payload,
+    ))?)
 }
 
-#[cfg(test)]
 impl Transmission {
-    pub fn get_value(&mut self) -> Result<(Gossip, bool), Error> {
-        match self {
-            Self::Push { payload, .. } => Ok((deserialize(payload)?, true)),
-            Self::Response { payload, .. } => Ok((deserialize(payload)?, false)),
+    /// Parses each of `frames`, checking their signatures as one batch.  The results are in the
+    /// order of `frames`.  Frames bigger than `limits` allow are rejected before being parsed,
+    /// and the body is decoded within the size of the frame, so that no length prefix can make
+    /// us allocate more.  Payloads sent in clear are decoded and checked against `limits`
+    /// before the signatures are verified, while encrypted ones can only be once decrypted.
+    pub fn deserialise_batch<P: PublicId>(
+        frames: &[(P, Vec<u8>)],
+        our_id: &Id,
+        verifier: &dyn Verifier<P>,
+        limits: &Limits,
+    ) -> Vec<Result<Transmission, Error>> {
+        let parsed: Vec<_> = frames
+            .iter()
+            .map(|(_, frame)| {
+                if frame.len() > limits.max_frame_size {
+                    return Err(Error::FrameTooLarge(frame.len()));
+                }
+                let mut transmission = Transmission::parse(frame)?;
+                transmission.limits = *limits;
+                if transmission.encryption == Encryption::None {
+                    transmission.message = Some(transmission.decode(&transmission.body.payload)?);
+                }
+                let signed = transmission.signed_bytes(our_id)?;
+                Ok((transmission, signed))
+            })
+            .collect();
+        let batch: Vec<_> = frames
+            .iter()
+            .zip(&parsed)
+            .filter_map(|((key, _), parsed)| {
+                let (transmission, signed) = parsed.as_ref().ok()?;
+                Some((key, &signed[..], &transmission.body.sig[..]))
+            })
+            .collect();
+        let mut verified = verifier.verify_batch(&batch).into_iter();
+        parsed
+            .into_iter()
+            .map(|parsed: Result<_, Error>| {
+                let (transmission, _) = parsed?;
+                verified.next().unwrap_or(Err(Error::SigFailure))?;
+                Ok(transmission)
+            })
+            .collect()
+    }
+
+    // The bytes the sender signed, if it sent this to `recipient`.
+    fn signed_bytes(&self, recipient: &Id) -> Result<Vec<u8>, Error> {
+        let header = Header {
+            version: self.version,
+            kind: self.kind,
+            codec: self.codec,
+            compression: self.compression,
+            encryption: self.encryption,
+            sequence: self.sequence,
+        };
+        signed_bytes(&header, &self.body.group, recipient, &self.body.payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gossip::{Content, InformedPlayer, ObliviousPlayer, Priority, Rumor, Topic};
+    use crate::identity::tests::TestId;
+    use crate::signing::{NativeSigner, NullSigner};
+    use crate::state::State;
+    use ed25519_dalek::{Keypair, PublicKey};
+    use std::time::{Duration, SystemTime};
+    use unwrap::unwrap;
+
+    const RECIPIENT: Id = Id([1; 32]);
+
+    fn envelope(version: u16, session: Option<&SessionKey>) -> Envelope<'_> {
+        Envelope {
+            recipient: RECIPIENT,
+            version,
+            sequence: 42,
+            session,
         }
     }
 
-    pub fn serialise(gossip: &Gossip, _is_push: bool, _keys: &Keypair) -> Result<Vec<u8>, Error> {
-        Ok(serialize(gossip)?)
+    fn serialise(
+        message: &Message,
+        envelope: &Envelope,
+        config: &Config,
+        keys: &Keypair,
+        metrics: &mut CompressionMetrics,
+    ) -> Result<Vec<u8>, Error> {
+        let group = GroupId::default();
+        Transmission::serialise(
+            &group,
+            message,
+            envelope,
+            config,
+            keys,
+            &NativeSigner,
+            metrics,
+        )
+    }
+
+    fn deserialise(frame: &[u8], key: &PublicKey) -> Result<Transmission, Error> {
+        deserialise_as(frame, key, &RECIPIENT, &NativeSigner)
     }
 
-    pub fn deserialise(payload: &[u8], _key: &PublicKey) -> Result<Transmission, Error> {
-        Ok(deserialize(&payload)?)
+    fn deserialise_as(
+        frame: &[u8],
+        key: &PublicKey,
+        our_id: &Id,
+        verifier: &dyn Verifier<PublicKey>,
+    ) -> Result<Transmission, Error> {
+        let frames = [(*key, frame.to_vec())];
+        let limits = Limits::default();
+        unwrap!(Transmission::deserialise_batch(&frames, our_id, verifier, &limits).pop())
+    }
+
+    fn frame(keys: &Keypair, version: u16) -> Vec<u8> {
+        let message = Message::BootstrapRequest(Id::from(keys.public));
+        let config = Config::default();
+        let mut metrics = CompressionMetrics::default();
+        unwrap!(serialise(
+            &message,
+            &envelope(version, None),
+            &config,
+            keys,
+            &mut metrics
+        ))
+    }
+
+    #[test]
+    fn frame_header() {
+        let keys = Keypair::generate::<sha3::Sha3_512, _>(&mut rand::thread_rng());
+        let frame = frame(&keys, PROTOCOL_VERSION);
+        assert_eq!(frame[..4], MAGIC);
+        assert_eq!(frame[4..6], PROTOCOL_VERSION.to_be_bytes());
+        assert_eq!(frame[6], Kind::BootstrapRequest as u8);
+
+        let mut transmission = unwrap!(deserialise(&frame, &keys.public));
+        assert_eq!(transmission.version, PROTOCOL_VERSION);
+        assert_eq!(transmission.sequence(), 42);
+        match unwrap!(transmission.get_value(None)) {
+            Message::BootstrapRequest(_) => (),
+            message => panic!("Unexpected message {:?}", message),
+        }
+    }
+
+    #[test]
+    fn invalid_frames() {
+        let keys = Keypair::generate::<sha3::Sha3_512, _>(&mut rand::thread_rng());
+        let mut bad_magic = frame(&keys, PROTOCOL_VERSION);
+        bad_magic[0] = b'X';
+        match Transmission::parse(&bad_magic) {
+            Err(Error::InvalidFrame) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        match Transmission::parse(&frame(&keys, PROTOCOL_VERSION + 1)) {
+            Err(Error::IncompatibleVersion(version)) => assert_eq!(version, PROTOCOL_VERSION + 1),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        let mut bad_kind = frame(&keys, PROTOCOL_VERSION);
+        bad_kind[6] = u8::MAX;
+        match Transmission::parse(&bad_kind) {
+            Err(Error::UnknownMessageType(kind)) => assert_eq!(kind, u8::MAX),
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn negotiate_version() {
+        assert_eq!(unwrap!(Versions::ours().negotiate()), PROTOCOL_VERSION);
+        let newer = Versions {
+            min: PROTOCOL_VERSION,
+            max: PROTOCOL_VERSION + 1,
+        };
+        assert_eq!(unwrap!(newer.negotiate()), PROTOCOL_VERSION);
+        let too_new = Versions {
+            min: PROTOCOL_VERSION + 1,
+            max: PROTOCOL_VERSION + 2,
+        };
+        assert!(too_new.negotiate().is_err());
+    }
+
+    #[test]
+    fn codecs() {
+        let mut rng = rand::thread_rng();
+        let keys = Keypair::generate::<sha3::Sha3_512, _>(&mut rng);
+        let id = Id::from(keys.public);
+        let content = Content::new(vec![1, 2, 3])
+            .with_ttl(Duration::from_secs(10))
+            .with_priority(Priority::High)
+            .with_topic(Topic("topic".to_string()));
+        let gossip = Gossip {
+            callee: ObliviousPlayer { id },
+            rumors: vec![Rumor {
+                content,
+                callee: ObliviousPlayer { id },
+                state: State::new(),
+                caller: InformedPlayer { id },
+            }],
+            caller: InformedPlayer { id },
+        };
+        let message = Message::Push(gossip);
+
+        let codecs = [
+            Codec::Bincode,
+            #[cfg(feature = "cbor")]
+            Codec::Cbor,
+            #[cfg(feature = "protobuf")]
+            Codec::Protobuf,
+        ];
+
+        for &codec in &codecs {
+            let config = Config {
+                codec,
+                ..Config::default()
+            };
+            let frame = unwrap!(serialise(
+                &message,
+                &envelope(PROTOCOL_VERSION, None),
+                &config,
+                &keys,
+                &mut CompressionMetrics::default()
+            ));
+            assert_eq!(frame[7], codec.id());
+            let mut transmission = unwrap!(deserialise(&frame, &keys.public));
+            assert_eq!(transmission.codec, codec);
+            match unwrap!(transmission.get_value(None)) {
+                Message::Push(received) => {
+                    let rumor = &received.rumors[0];
+                    assert_eq!(rumor.content.value, vec![1, 2, 3]);
+                    assert_eq!(rumor.content.priority, Priority::High);
+                    assert!(rumor.content.deadline.unwrap() > SystemTime::now());
+                    assert_eq!(rumor.state, State::new());
+                }
+                message => panic!("Unexpected message {:?}", message),
+            }
+        }
+    }
+
+    #[test]
+    fn compression() {
+        let mut rng = rand::thread_rng();
+        let keys = Keypair::generate::<sha3::Sha3_512, _>(&mut rng);
+        let id = Id::from(keys.public);
+        let rumor = Rumor {
+            content: Content::new(vec![7; 4096]),
+            callee: ObliviousPlayer { id },
+            state: State::new(),
+            caller: InformedPlayer { id },
+        };
+        let message = Message::Push(Gossip {
+            callee: ObliviousPlayer { id },
+            rumors: vec![rumor],
+            caller: InformedPlayer { id },
+        });
+
+        let compressions = [
+            Compression::None,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd,
+            #[cfg(feature = "lz4")]
+            Compression::Lz4,
+        ];
+
+        for &compression in &compressions {
+            let config = Config {
+                compression,
+                ..Config::default()
+            };
+            let mut metrics = CompressionMetrics::default();
+            let frame = unwrap!(serialise(
+                &message,
+                &envelope(PROTOCOL_VERSION, None),
+                &config,
+                &keys,
+                &mut metrics
+            ));
+            assert_eq!(frame[8], compression.id());
+            if compression != Compression::None {
+                assert_eq!(metrics.compressed, 1);
+                assert!(frame.len() < 4096);
+                assert!(unwrap!(metrics.ratio()) < 1.0);
+            }
+            let mut transmission = unwrap!(deserialise(&frame, &keys.public));
+            match unwrap!(transmission.get_value(None)) {
+                Message::Push(received) => {
+                    assert_eq!(received.rumors[0].content.value, vec![7; 4096])
+                }
+                message => panic!("Unexpected message {:?}", message),
+            }
+
+            // Payloads below the threshold aren't compressed.
+            let frame = unwrap!(serialise(
+                &Message::BootstrapRequest(id),
+                &envelope(PROTOCOL_VERSION, None),
+                &config,
+                &keys,
+                &mut metrics
+            ));
+            assert_eq!(frame[8], Compression::None.id());
+        }
+    }
+
+    #[test]
+    fn encryption() {
+        let mut rng = rand::thread_rng();
+        let alice = Keypair::generate::<sha3::Sha3_512, _>(&mut rng);
+        let bob = Keypair::generate::<sha3::Sha3_512, _>(&mut rng);
+        let secret = b"a confidential rumor".to_vec();
+        let message = Message::Subscriptions(Subscriptions {
+            player: Id::from(alice.public),
+            topics: vec![Topic(String::from_utf8_lossy(&secret).into_owned())]
+                .into_iter()
+                .collect(),
+        });
+        let config = Config {
+            encryption: Encryption::XChaCha20Poly1305,
+            ..Config::default()
+        };
+        let alice_session = unwrap!(SessionKey::derive(&alice, &bob.public));
+        let bob_session = unwrap!(SessionKey::derive(&bob, &alice.public));
+
+        let frame = unwrap!(serialise(
+            &message,
+            &envelope(PROTOCOL_VERSION, Some(&alice_session)),
+            &config,
+            &alice,
+            &mut CompressionMetrics::default()
+        ));
+        assert_eq!(frame[9], Encryption::XChaCha20Poly1305.id());
+        assert!(!frame
+            .windows(secret.len())
+            .any(|window| window == &secret[..]));
+
+        let mut transmission = unwrap!(deserialise(&frame, &alice.public));
+        assert_eq!(transmission.encryption(), Encryption::XChaCha20Poly1305);
+        match transmission.get_value(None) {
+            Err(Error::DecryptionFailure) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        match unwrap!(transmission.get_value(Some(&bob_session))) {
+            Message::Subscriptions(subscriptions) => {
+                assert_eq!(subscriptions.player, Id::from(alice.public))
+            }
+            message => panic!("Unexpected message {:?}", message),
+        }
+    }
+
+    #[test]
+    fn key_rotation() {
+        let mut rng = rand::thread_rng();
+        let keys = Keypair::generate::<sha3::Sha3_512, _>(&mut rng);
+        let new_keys = Keypair::generate::<sha3::Sha3_512, _>(&mut rng);
+        let proof = unwrap!(KeyRotation::proof(
+            &Id::from(keys.public),
+            &Id::from(new_keys.public)
+        ));
+        let rotation = KeyRotation {
+            public_id: new_keys.public.to_bytes().to_vec(),
+            sig: SecretId::sign(&new_keys, &proof),
+        };
+        let message = Message::KeyRotation(rotation.clone());
+        let codecs = [
+            Codec::Bincode,
+            #[cfg(feature = "cbor")]
+            Codec::Cbor,
+            #[cfg(feature = "protobuf")]
+            Codec::Protobuf,
+        ];
+        for &codec in &codecs {
+            let config = Config {
+                codec,
+                ..Config::default()
+            };
+            let frame = unwrap!(serialise(
+                &message,
+                &envelope(PROTOCOL_VERSION, None),
+                &config,
+                &keys,
+                &mut CompressionMetrics::default()
+            ));
+            // Signed with the old key.
+            let mut transmission = unwrap!(deserialise(&frame, &keys.public));
+            match unwrap!(transmission.get_value(None)) {
+                Message::KeyRotation(received) => assert_eq!(received, rotation),
+                message => panic!("Unexpected message {:?}", message),
+            }
+        }
+    }
+
+    #[test]
+    fn limits() {
+        let keys = Keypair::generate::<sha3::Sha3_512, _>(&mut rand::thread_rng());
+        let id = Id::from(keys.public);
+        let rumor = |value| Rumor {
+            content: Content::new(value),
+            callee: ObliviousPlayer { id },
+            state: State::new(),
+            caller: InformedPlayer { id },
+        };
+        let gossip = Gossip {
+            callee: ObliviousPlayer { id },
+            rumors: vec![rumor(vec![1; 10]), rumor(vec![2; 100])],
+            caller: InformedPlayer { id },
+        };
+        let frame = unwrap!(serialise(
+            &Message::Push(gossip),
+            &envelope(PROTOCOL_VERSION, None),
+            &Config::default(),
+            &keys,
+            &mut CompressionMetrics::default()
+        ));
+        let frames = [(keys.public, frame.clone())];
+        let decode = |limits: Limits| {
+            let mut results =
+                Transmission::deserialise_batch(&frames, &RECIPIENT, &NativeSigner, &limits);
+            unwrap!(results.pop()).and_then(|mut transmission| transmission.get_value(None))
+        };
+        assert!(decode(Limits::default()).is_ok());
+        match decode(Limits {
+            max_frame_size: frame.len() - 1,
+            ..Limits::default()
+        }) {
+            Err(Error::FrameTooLarge(size)) => assert_eq!(size, frame.len()),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        match decode(Limits {
+            max_rumors_per_gossip: 1,
+            ..Limits::default()
+        }) {
+            Err(Error::TooManyRumors(2)) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        match decode(Limits {
+            max_content_size: 99,
+            ..Limits::default()
+        }) {
+            Err(Error::ContentTooLarge(100)) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        // The limits are checked before the signature.
+        let mut forged = frame.clone();
+        let last = forged.len() - 1;
+        forged[last] ^= 1;
+        let limits = Limits {
+            max_rumors_per_gossip: 1,
+            ..Limits::default()
+        };
+        let frames = [(keys.public, forged)];
+        match unwrap!(
+            Transmission::deserialise_batch(&frames, &RECIPIENT, &NativeSigner, &limits).pop()
+        ) {
+            Err(Error::TooManyRumors(2)) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        // A length prefix beyond the end of the frame is rejected without allocating for it.
+        let mut huge = frame[..HEADER_LEN].to_vec();
+        huge.extend_from_slice(&[0xff; 8]);
+        match Transmission::parse(&huge) {
+            Err(Error::Serialisation(_)) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn signatures() {
+        let keys = Keypair::generate::<sha3::Sha3_512, _>(&mut rand::thread_rng());
+        let other_keys = Keypair::generate::<sha3::Sha3_512, _>(&mut rand::thread_rng());
+        let frame = frame(&keys, PROTOCOL_VERSION);
+        assert!(deserialise(&frame, &keys.public).is_ok());
+
+        let mut tampered = frame.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        for (frame, key, our_id) in [
+            (&tampered, &keys.public, RECIPIENT),
+            (&frame, &other_keys.public, RECIPIENT),
+            (&frame, &keys.public, Id::from(other_keys.public)),
+        ] {
+            match deserialise_as(frame, key, &our_id, &NativeSigner) {
+                Err(Error::SigFailure) => (),
+                result => panic!("Unexpected result {:?}", result),
+            }
+        }
+
+        // The codec, compression and encryption bytes are signed with the rest of the header.
+        let mut tampered = frame.clone();
+        tampered[9] = Encryption::XChaCha20Poly1305.id();
+        match deserialise(&tampered, &keys.public) {
+            Err(Error::SigFailure) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        // The null signer's blank signatures only pass its own verification.
+        let message = Message::BootstrapRequest(Id::from(keys.public));
+        let frame = unwrap!(Transmission::serialise(
+            &GroupId::default(),
+            &message,
+            &envelope(PROTOCOL_VERSION, None),
+            &Config::default(),
+            &keys,
+            &NullSigner,
+            &mut CompressionMetrics::default()
+        ));
+        assert!(deserialise(&frame, &keys.public).is_err());
+        assert!(deserialise_as(&frame, &keys.public, &RECIPIENT, &NullSigner).is_ok());
+    }
+
+    #[test]
+    fn batch() {
+        let keys = Keypair::generate::<sha3::Sha3_512, _>(&mut rand::thread_rng());
+        let mut frames: Vec<_> = (0..4)
+            .map(|_| (keys.public, frame(&keys, PROTOCOL_VERSION)))
+            .collect();
+        let last = frames[1].1.len() - 1;
+        frames[1].1[last] ^= 1;
+        frames[2].1[0] = b'X';
+
+        let results =
+            Transmission::deserialise_batch(&frames, &RECIPIENT, &NativeSigner, &Limits::default());
+        assert_eq!(results.len(), 4);
+        assert!(results[0].is_ok());
+        match results[1] {
+            Err(Error::SigFailure) => (),
+            ref result => panic!("Unexpected result {:?}", result),
+        }
+        match results[2] {
+            Err(Error::InvalidFrame) => (),
+            ref result => panic!("Unexpected result {:?}", result),
+        }
+        assert!(results[3].is_ok());
+    }
+
+    #[test]
+    fn other_identities() {
+        let (sender, other) = (TestId(1), TestId(2));
+        let message = Message::BootstrapRequest(sender.id());
+        let frame = unwrap!(Transmission::serialise(
+            &GroupId::default(),
+            &message,
+            &envelope(PROTOCOL_VERSION, None),
+            &Config::default(),
+            &sender,
+            &NativeSigner,
+            &mut CompressionMetrics::default()
+        ));
+        let frames = vec![(sender, frame.clone()), (other, frame)];
+        let mut results =
+            Transmission::deserialise_batch(&frames, &RECIPIENT, &NativeSigner, &Limits::default());
+        assert!(results.pop().is_some_and(|result| result.is_err()));
+        match unwrap!(unwrap!(unwrap!(results.pop())).get_value(None)) {
+            Message::BootstrapRequest(id) => assert_eq!(id, sender.id()),
+            message => panic!("Unexpected message {:?}", message),
+        }
     }
 }